@@ -1,39 +1,207 @@
 use std::path::PathBuf;
 
-use clap::{crate_name, value_parser, Parser, ValueEnum};
+use std::ffi::OsStr;
+
+use clap::{crate_name, value_parser, Args, Parser, Subcommand, ValueEnum};
+use globset::{Glob, GlobBuilder};
 use reqwest::Proxy;
 
+use crate::filter::{Filter, TextField, Values};
+use crate::pattern::PathPattern;
+use crate::rewrite::Rewrites;
+use crate::template::LrcTemplate;
+
 #[derive(Debug, Parser)]
-#[command(name = crate_name!(), version, about)]
+#[command(
+    name = crate_name!(),
+    version,
+    about,
+    subcommand_negates_reqs = true,
+)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Paths to scan. Could be a mix files or directories. If it's a directory, this program will
     /// traverse it recursively and download .lrc files, reporting any errors along the way. If it's
-    /// a file, will download a corresponding .lrc file for it
-    #[arg(required = true)]
+    /// a file, will download a corresponding .lrc file for it. M3U and M3U8 playlists are replaced
+    /// with the tracks they reference
+    #[arg(required_unless_present = "from_stdin")]
     pub paths: Vec<PathBuf>,
 
+    /// Read paths of tracks from stdin, one per line, instead of scanning PATHS. Directories
+    /// aren't traversed, and tracks are processed as soon as their paths are read
+    #[arg(long, conflicts_with = "paths")]
+    pub from_stdin: bool,
+
+    /// Paths read with --from-stdin are separated by NUL instead of newlines, like the output of
+    /// `find -print0`
+    #[arg(short = '0', long, requires = "from_stdin", conflicts_with = "paths")]
+    pub null: bool,
+
     /// Don't ignore hidden files and directories
-    #[arg(short = 'i', long)]
+    #[arg(short = 'i', long, global = true)]
     pub no_ignore_hidden: bool,
 
     /// Don't follow symlinks
-    #[arg(short = 's', long)]
+    #[arg(short = 's', long, global = true)]
     pub no_follow_symlinks: bool,
 
+    /// Match patterns in .ignore files case-sensitively
+    #[arg(long, global = true)]
+    pub case_sensitive_ignore: bool,
+
+    /// Respect .gitignore files too, even outside of git repositories
+    #[arg(long, global = true)]
+    pub git_ignore: bool,
+
     /// .lrc file acquisition behavior
-    #[arg(short = 'l', long, value_enum, default_value_t = LrcAcquireBehavior::LrcMissing)]
+    #[arg(
+        short = 'l',
+        long,
+        value_enum,
+        default_value_t = LrcAcquireBehavior::LrcMissing,
+        global = true,
+    )]
     pub lrc_acquire_behavior: LrcAcquireBehavior,
 
     /// Allows the program to create .nolrc files, in order to prevent requesting lyrics from the
     /// same songs in the future, making the process faster if you keep a large library. As a
-    /// downside, you get a lot of empty .nolrc files
-    #[arg(long)]
+    /// downside, you get a lot of .nolrc files
+    #[arg(long, global = true)]
     pub deny_nolrc: bool,
 
+    /// Don't show the progress. Otherwise, it's shown as a progress bar, if stderr is a terminal,
+    /// or as a status line every 10 seconds
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+
+    /// Format of reports on stdout. Either way, a summary of outcomes and failed tracks is
+    /// printed at the end. With `json`, every track is also printed as a JSON object on its own
+    /// line
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub output: OutputFormat,
+
+    /// Request lyrics again for tracks, whose .nolrc file says LRCLIB didn't have them, or that
+    /// were marked as instrumental without asking LRCLIB. Tracks LRCLIB knows as instrumental are
+    /// still skipped
+    #[arg(long, global = true)]
+    pub retry_not_found: bool,
+
+    /// Write an .lrc file with `[instrumental]` for instrumental tracks instead of a .nolrc file,
+    /// for players that display it. Tracks guessed to be instrumental still get a .nolrc file
+    #[arg(long, global = true)]
+    pub instrumental_lrc: bool,
+
+    /// Name of lyrics files, without the extension. Supports `{stem}`, `{filename}`, `{artist}`,
+    /// `{title}`, `{track}` and `{lang}` placeholders. For example, use `{filename}` to get
+    /// `song.flac.lrc` instead of `song.lrc`. Tracks that resolve to the same lyrics file are
+    /// reported and skipped
+    #[arg(long, default_value = "{stem}{lang}", global = true)]
+    pub lrc_name: LrcTemplate,
+
+    /// Language code, substituted as a `.<LANG>` suffix for `{lang}` in --lrc-name
+    #[arg(long, value_parser = lang, global = true)]
+    pub lang: Option<String>,
+
+    /// Alternative names of existing lyrics files, including the extension. Supports the same
+    /// placeholders as --lrc-name. Tracks with such files are treated as if they had a .lrc file.
+    /// Lyrics file names are always matched ignoring case
+    #[arg(long, default_values = ["{filename}.lrc", "{stem}.txt"], global = true)]
+    pub alt_lrc_name: Vec<LrcTemplate>,
+
+    /// Pattern of track paths, used to fill in the artist, album, track number and title, when
+    /// tags are missing. Matched against the end of the path without the extension, the first
+    /// matching pattern is used. Supports `{artist}`, `{album}`, `{title}`, `{track}` and `{_}`
+    /// placeholders, each matching a part of a single path component. For example,
+    /// `{artist}/{album}/{track} - {title}`. Tags always take precedence
+    #[arg(long, global = true)]
+    pub path_pattern: Vec<PathPattern>,
+
+    /// Separators of individual artists in artist tags. If there are no lyrics for the full artist
+    /// tag, each individual artist is tried one by one. Separators like " & " or ", " are common in
+    /// names of single artists, like "Simon & Garfunkel", so they have to be passed explicitly
+    #[arg(long, default_values = [";", " feat. ", " ft. "], global = true)]
+    pub artist_separator: Vec<String>,
+
+    /// TOML file with rules, that rewrite the artist, title and album before requesting lyrics.
+    /// For example, to turn "Beatles, The" into "The Beatles". Names of lyrics files are still
+    /// based on the original tags
+    #[arg(long, value_name = "FILE", value_parser = rewrite_rules, global = true)]
+    pub rewrite_rules: Option<Rewrites>,
+
+    /// Don't split single-file rips into tracks, described by CUE sheets next to them. By default,
+    /// lyrics are requested for every CUE track and saved as `<stem>.<track number>.lrc`
+    #[arg(long, global = true)]
+    pub no_cue: bool,
+
+    /// Only look for lyrics files named according to --lrc-name, ignoring --alt-lrc-name
+    #[arg(long, global = true)]
+    pub no_alt_lrc_names: bool,
+
+    /// Don't write or delete anything, only report what would be done and why
+    #[arg(short = 'n', long, global = true)]
+    pub dry_run: bool,
+
+    /// Don't request lyrics either, only report which tracks would be requested. Requires
+    /// --dry-run
+    #[arg(long, requires = "dry_run", global = true)]
+    pub offline: bool,
+
+    /// Don't record changes to lyrics files. Without the journal, they can't be undone
+    #[arg(long, global = true)]
+    pub no_journal: bool,
+
+    /// Directory, where journals of changes made during each run are kept. Defaults to
+    /// `cnova/journal` inside of the platform-specific local data directory
+    #[arg(long, global = true)]
+    pub journal_dir: Option<PathBuf>,
+
+    /// How many of the latest runs can be undone. Journals of older runs, along with their
+    /// backups, are deleted
+    #[arg(
+        long,
+        global = true,
+        default_value_t = 20,
+        value_parser = value_parser!(u16).range(1..),
+    )]
+    pub keep_journals: u16,
+
+    /// Read tags of every track again, even if it didn't change since the last run. The index
+    /// is rebuilt from scratch
+    #[arg(long, global = true)]
+    pub full_rescan: bool,
+
+    /// Don't use the index of previous runs at all
+    #[arg(long, conflicts_with = "full_rescan", global = true)]
+    pub no_index: bool,
+
+    /// File, where results of previous runs are kept, so that unchanged tracks can be skipped.
+    /// Defaults to `cnova/index.json` inside of the platform-specific cache directory
+    #[arg(long, value_name = "FILE", global = true)]
+    pub index_file: Option<PathBuf>,
+
     /// File matching strictness level
-    #[arg(long, value_enum, default_value_t = FileMatchStrictness::FilterByExt)]
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = FileMatchStrictness::FilterByExt,
+        global = true,
+    )]
     pub strictness: FileMatchStrictness,
 
+    #[command(flatten)]
+    pub exts: AudioExts,
+
+    #[command(flatten)]
+    pub walk: WalkFilters,
+
+    #[command(flatten)]
+    pub tags: TagFilters,
+
+    #[command(flatten)]
+    pub instrumental: InstrumentalGuess,
+
     /// How many simultaneous downloads will occur at the same time. The default value is selected
     /// to not, hopefully, overwhelm the website with traffic
     #[arg(
@@ -41,23 +209,287 @@ pub struct Cli {
         long,
         default_value_t = 5,
         value_parser = value_parser!(u16).range(1..),
+        global = true,
     )]
     pub download_jobs: u16,
 
     /// How many threads will be spawn to process the files. 0 corresponds to the amount of
     /// available system threads
-    #[arg(short = 'J', long, default_value_t = 0)]
+    #[arg(short = 'J', long, default_value_t = 0, global = true)]
     pub traversal_jobs: u16,
 
     /// Proxy setting, supports SOCKS5, SOCKS4 and HTTP proxies
-    #[arg(short, long, value_parser = proxy)]
+    #[arg(short, long, value_parser = proxy, global = true)]
     pub proxy: Option<reqwest::Proxy>,
+
+    /// TOML file with default values of these options. Defaults to `cnova/config.toml` inside of
+    /// the platform-specific config directory, if it exists
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Named profile of the config file to use on top of its top-level values
+    #[arg(long, value_name = "NAME", global = true)]
+    pub profile: Option<String>,
+}
+
+/// Extensions of files, that are treated as tracks. By default, these are all the extensions
+/// supported by lofty
+#[derive(Clone, Debug, Default, Args)]
+pub struct AudioExts {
+    /// Additional extensions of audio files, separated by commas. Files with extensions unknown
+    /// to lofty are probed by their contents
+    #[arg(long, value_delimiter = ',', value_parser = ext, global = true)]
+    pub include_ext: Vec<String>,
+
+    /// Extensions of audio files to skip, separated by commas. Takes precedence over
+    /// --include-ext
+    #[arg(long, value_delimiter = ',', value_parser = ext, global = true)]
+    pub exclude_ext: Vec<String>,
+}
+
+impl AudioExts {
+    pub fn contains(&self, ext: &OsStr) -> bool {
+        let matches = |e: &String| ext.eq_ignore_ascii_case(e);
+
+        !self.exclude_ext.iter().any(matches)
+            && (self.include_ext.iter().any(matches)
+                || lofty::file::EXTENSIONS
+                    .iter()
+                    .any(|e| ext.eq_ignore_ascii_case(e)))
+    }
+}
+
+/// Filters of the directory walker, on top of ignore files
+#[derive(Clone, Debug, Default, Args)]
+pub struct WalkFilters {
+    /// Only treat files matching this glob as tracks, e.g. `*.flac`. Can be repeated. Globs
+    /// without a `/` are matched against file names, others against whole paths
+    #[arg(long, value_name = "GLOB", value_parser = glob, global = true)]
+    pub include: Vec<Glob>,
+
+    /// Skip files and directories matching this glob, e.g. `Audiobooks` or `**/Podcasts/*.mp3`.
+    /// Can be repeated. Takes precedence over --include
+    #[arg(long, value_name = "GLOB", value_parser = glob, global = true)]
+    pub exclude: Vec<Glob>,
+
+    /// Don't descend more than this many directories into PATHS
+    #[arg(long, value_name = "DEPTH", global = true)]
+    pub max_depth: Option<usize>,
+
+    /// Skip files larger than this, e.g. `500M`. Supports K, M and G suffixes
+    #[arg(long, value_name = "SIZE", value_parser = size, global = true)]
+    pub max_filesize: Option<u64>,
+
+    /// Skip directories containing a file with this name, along with everything inside them.
+    /// Can be repeated
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = marker,
+        default_values = [".nolyrics"],
+        global = true,
+    )]
+    pub skip_marker: Vec<String>,
+}
+
+/// Filters of tracks by their tags, applied once the tags are read
+#[derive(Clone, Debug, Default, Args)]
+pub struct TagFilters {
+    /// Only fetch lyrics for tracks matching this expression, e.g.
+    /// `genre != "Classical" && year >= 1990 && duration < 20m`. Can be repeated, in which case
+    /// all of them have to match. See the README for the syntax
+    #[arg(long, value_name = "EXPR", global = true)]
+    pub filter: Vec<Filter>,
+
+    /// Only fetch lyrics for tracks, whose artist matches this glob, ignoring case. Can be
+    /// repeated, in which case any of them has to match
+    #[arg(long, value_name = "GLOB", value_parser = artist_glob, global = true)]
+    pub artist: Vec<Filter>,
+
+    /// Same as --artist, but for albums
+    #[arg(long, value_name = "GLOB", value_parser = album_glob, global = true)]
+    pub album: Vec<Filter>,
+
+    /// Same as --artist, but for genres
+    #[arg(long, value_name = "GLOB", value_parser = genre_glob, global = true)]
+    pub genre: Vec<Filter>,
+}
+
+impl TagFilters {
+    pub fn matches(&self, values: &Values) -> bool {
+        let any =
+            |filters: &[Filter]| filters.is_empty() || filters.iter().any(|f| f.matches(values));
+
+        self.filter.iter().all(|f| f.matches(values))
+            && any(&self.artist)
+            && any(&self.album)
+            && any(&self.genre)
+    }
+}
+
+/// Local detection of instrumental tracks, which saves a request for each of them
+#[derive(Clone, Debug, Default, Args)]
+pub struct InstrumentalGuess {
+    /// Treat tracks, that look instrumental by their tags, as such, without requesting lyrics
+    #[arg(long, global = true)]
+    pub guess_instrumental: bool,
+
+    /// Words in titles of instrumental tracks, used with --guess-instrumental. Can be repeated
+    #[arg(
+        long,
+        value_name = "WORD",
+        default_values = ["Instrumental", "Interlude", "Intro", "Off Vocal", "Karaoke"],
+        global = true,
+    )]
+    pub instrumental_keyword: Vec<String>,
+
+    /// Words in genres of instrumental tracks, used with --guess-instrumental. Can be repeated
+    #[arg(
+        long,
+        value_name = "WORD",
+        default_values = ["Instrumental"],
+        global = true,
+    )]
+    pub instrumental_genre: Vec<String>,
+
+    /// Treat tracks shorter than this many seconds as instrumental, used with
+    /// --guess-instrumental
+    #[arg(long, value_name = "SECONDS", global = true)]
+    pub min_vocal_duration: Option<u64>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Revert all the changes to lyrics files, made during a single run
+    Undo {
+        /// Id of the run to revert, as printed at the end of the run. Defaults to the latest run
+        run_id: Option<String>,
+    },
+
+    /// Delete .lrc and .nolrc files, whose tracks don't exist anymore
+    Clean {
+        /// Paths to scan for orphaned lyrics files. Directories are traversed the same way as
+        /// when downloading lyrics
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Report how many tracks have synced, plain or no lyrics, without requesting anything
+    Stats {
+        /// Paths to scan for tracks. Directories are traversed the same way as when downloading
+        /// lyrics
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Format of the report
+        #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+        format: StatsFormat,
+    },
+
+    /// Keep running and download lyrics for new tracks as soon as they appear
+    Watch {
+        /// Directories to watch for new tracks, recursively
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// How long a new file has to stay unchanged before it's considered fully written
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..),
+        )]
+        settle: u64,
+    },
+
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the values of all the options, taking the config file, the profile and the command
+    /// line into account
+    Show,
 }
 
 fn proxy(s: &str) -> Result<Proxy, String> {
     Proxy::all(s).map_err(|_| "invalid proxy string".to_string())
 }
 
+fn ext(s: &str) -> Result<String, String> {
+    let s = s.strip_prefix('.').unwrap_or(s);
+    if s.is_empty() || s.contains(['/', '\\', '.']) {
+        Err("extension can't be empty or contain '/', '\\' or '.'".to_string())
+    } else {
+        Ok(s.to_ascii_lowercase())
+    }
+}
+
+fn glob(s: &str) -> Result<Glob, String> {
+    GlobBuilder::new(s)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| e.kind().to_string())
+}
+
+fn artist_glob(s: &str) -> Result<Filter, String> {
+    Filter::glob(TextField::Artist, s).map_err(|e| e.to_string())
+}
+
+fn album_glob(s: &str) -> Result<Filter, String> {
+    Filter::glob(TextField::Album, s).map_err(|e| e.to_string())
+}
+
+fn genre_glob(s: &str) -> Result<Filter, String> {
+    Filter::glob(TextField::Genre, s).map_err(|e| e.to_string())
+}
+
+fn size(s: &str) -> Result<u64, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("unknown size unit \"{}\"", unit)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| "invalid size".to_string())
+}
+
+fn marker(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains(['/', '\\']) {
+        Err("marker has to be a file name".to_string())
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+fn rewrite_rules(s: &str) -> Result<Rewrites, String> {
+    Rewrites::load(s.as_ref()).map_err(|e| e.to_string())
+}
+
+fn lang(s: &str) -> Result<String, String> {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(s.to_owned())
+    } else {
+        Err("language code can only contain ASCII letters, digits, '-' and '_'".to_string())
+    }
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 pub enum FileMatchStrictness {
     /// Try to probe any file by it's extension, even if it doesn't match. Not recommended
@@ -68,12 +500,31 @@ pub enum FileMatchStrictness {
     Paranoid,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for FileMatchStrictness {
     fn default() -> Self {
         Self::FilterByExt
     }
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum StatsFormat {
+    /// Human readable tables
+    Text,
+    /// Comma separated values, one row per scope: the total, each artist and each album
+    Csv,
+    /// Standalone HTML page
+    Html,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    /// Log lines, followed by a summary
+    Text,
+    /// JSON lines, one per track
+    Json,
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 pub enum LrcAcquireBehavior {
     /// Attempt to download lyrics for every track, even if a corresponding .lrc or .nolrc is present
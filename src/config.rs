@@ -0,0 +1,430 @@
+use crate::cli::Cli;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory as _, FromArgMatches as _};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
+
+/// Options, that only make sense for a single invocation, and can't be set in the config
+const NOT_CONFIGURABLE: &[&str] = &[
+    "paths",
+    "from_stdin",
+    "null",
+    "config",
+    "profile",
+    "help",
+    "version",
+];
+
+/// Default location of the config file
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(clap::crate_name!()).join("config.toml"))
+}
+
+/// Default values of command line options, read from a TOML file. Keys are the names of long
+/// options, without the leading dashes. Named profiles in `[profile.<name>]` tables take
+/// precedence over the top-level values, when selected
+///
+/// ```toml
+/// download-jobs = 2
+/// exclude-ext = ["m4b"]
+///
+/// [profile.car]
+/// lrc-acquire-behavior = "all"
+/// deny-nolrc = true
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    defaults: Table,
+    profiles: HashMap<String, Table>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to parse config: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("\"profile\" has to be a table of profiles")]
+    Profiles,
+    #[error("unknown profile \"{0}\"")]
+    UnknownProfile(String),
+    #[error("unknown option \"{0}\"")]
+    UnknownOption(String),
+    #[error("option \"{0}\" has an unsupported value")]
+    Value(String),
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut defaults: Table = toml::from_str(s)?;
+
+        let profiles = match defaults.remove("profile") {
+            None => HashMap::new(),
+            Some(Value::Table(profiles)) => profiles
+                .into_iter()
+                .map(|(name, profile)| match profile {
+                    Value::Table(profile) => Ok((name, profile)),
+                    _ => Err(ConfigError::Profiles),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(ConfigError::Profiles),
+        };
+
+        Ok(Self { defaults, profiles })
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        std::fs::read_to_string(path)
+            .map_err(|source| ConfigError::Read {
+                path: path.to_owned(),
+                source,
+            })?
+            .parse()
+    }
+
+    /// Top-level values, overridden by the ones from `profile`, if any
+    fn settings(&self, profile: Option<&str>) -> Result<Table, ConfigError> {
+        let mut res = self.defaults.clone();
+        if let Some(name) = profile {
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownProfile(name.to_owned()))?;
+            res.extend(profile.clone());
+        }
+        Ok(res)
+    }
+}
+
+/// Where each of the [`Cli`] values came from
+#[derive(Debug)]
+pub struct Settings {
+    matches: ArgMatches,
+    /// Config file, that was read, if any
+    path: Option<PathBuf>,
+    /// Ids of the options, whose values came from the config
+    from_config: Vec<String>,
+}
+
+/// Parses `args` on top of the config file. The file is picked with `--config`, or found at
+/// [`default_path`]. Exits on errors, just like [`clap::Parser::parse`]
+pub fn parse<I>(args: I) -> (Cli, Settings)
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let args: Vec<_> = args.into_iter().collect();
+
+    // The config has to be found first, but also validated along with everything else
+    let matches = command().get_matches_from(&args);
+    let explicit = global::<PathBuf>(&matches, "config");
+    let profile = global::<String>(&matches, "profile");
+
+    let path = explicit.clone().or_else(default_path);
+    let config = match &path {
+        Some(path) if explicit.is_some() || path.exists() => {
+            Some(Config::load(path).unwrap_or_else(|e| exit(e)))
+        }
+        _ => None,
+    };
+    if config.is_none() && profile.is_some() {
+        exit(ConfigError::UnknownProfile(profile.unwrap_or_default()));
+    }
+
+    let mut command = command();
+    let mut from_config = Vec::new();
+    if let Some(config) = &config {
+        let settings = config
+            .settings(profile.as_deref())
+            .unwrap_or_else(|e| exit(e));
+        for (key, value) in settings {
+            let id = key.replace('-', "_");
+            let known = command
+                .get_arguments()
+                .any(|arg| arg.get_id() == id.as_str() && !arg.is_positional());
+            if !known || NOT_CONFIGURABLE.contains(&id.as_str()) {
+                exit(ConfigError::UnknownOption(key));
+            }
+
+            let values = values(&value).ok_or_else(|| ConfigError::Value(key.clone()));
+            let values = values.unwrap_or_else(|e| exit(e));
+            command = command.mut_arg(&id, |arg| arg.default_values(values));
+            from_config.push(id);
+        }
+    }
+
+    let matches = command.get_matches_from(&args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli.offline && !cli.dry_run {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--offline requires --dry-run",
+            )
+            .exit();
+    }
+
+    let settings = Settings {
+        matches,
+        path: config.and(path),
+        from_config,
+    };
+    (cli, settings)
+}
+
+impl Settings {
+    /// Effective values of all the options, in the config format. Each one is annotated with
+    /// where it came from
+    pub fn show(&self) -> String {
+        let mut res = String::new();
+        if let Some(path) = &self.path {
+            let _ = writeln!(res, "# config: {}", path.display());
+        }
+        if let Some(profile) = global::<String>(&self.matches, "profile") {
+            let _ = writeln!(res, "# profile: {}", profile);
+        }
+
+        let command = Cli::command();
+        for arg in command.get_arguments() {
+            let id = arg.get_id().as_str();
+            if arg.is_positional() || NOT_CONFIGURABLE.contains(&id) {
+                continue;
+            }
+
+            let Some(raw) = self.matches.get_raw(id) else {
+                continue;
+            };
+            let raw: Vec<_> = raw.map(|value| value.to_string_lossy()).collect();
+            let value = match arg.get_action() {
+                ArgAction::Append => Value::Array(raw.iter().map(|v| typed(v)).collect()),
+                _ => match raw.first() {
+                    Some(v) => typed(v),
+                    None => continue,
+                },
+            };
+
+            let source = match self.matches.value_source(id) {
+                Some(ValueSource::CommandLine) => "command line",
+                _ if self.from_config.iter().any(|c| c == id) => "config",
+                _ => "default",
+            };
+            let key = arg.get_long().unwrap_or(id);
+            let _ = writeln!(res, "{} = {} # {}", key, value, source);
+        }
+
+        res
+    }
+}
+
+/// Command line options of [`Cli`]. Flags also take an optional value, like `--deny-nolrc=false`,
+/// so the ones turned on in the config can be turned off for a single run
+fn command() -> clap::Command {
+    let flags: Vec<_> = Cli::command()
+        .get_arguments()
+        .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+        .filter(|arg| !NOT_CONFIGURABLE.contains(&arg.get_id().as_str()))
+        .map(|arg| arg.get_id().clone())
+        .collect();
+
+    flags.into_iter().fold(Cli::command(), |command, id| {
+        command.mut_arg(id, |arg| {
+            arg.action(ArgAction::Set)
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_name("BOOL")
+                .default_value("false")
+                .default_missing_value("true")
+        })
+    })
+}
+
+/// Value of a global option, wherever it was specified
+fn global<T>(matches: &ArgMatches, id: &str) -> Option<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let mut matches = Some(matches);
+    while let Some(m) = matches {
+        if let Some(value) = m.get_one::<T>(id) {
+            return Some(value.clone());
+        }
+        matches = m.subcommand().map(|(_, m)| m);
+    }
+    None
+}
+
+/// Command line values of a config value
+fn values(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(s) => Some(vec![s.clone()]),
+        Value::Integer(i) => Some(vec![i.to_string()]),
+        Value::Float(f) => Some(vec![f.to_string()]),
+        Value::Boolean(b) => Some(vec![b.to_string()]),
+        Value::Array(array) => array
+            .iter()
+            .map(|value| match value {
+                Value::Array(_) => None,
+                value => values(value).and_then(|v| v.into_iter().next()),
+            })
+            .collect(),
+        Value::Datetime(_) | Value::Table(_) => None,
+    }
+}
+
+/// Config value of a command line value. Numbers and booleans are written as such
+fn typed(raw: &str) -> Value {
+    if let Ok(b) = raw.parse() {
+        Value::Boolean(b)
+    } else if let Ok(i) = raw.parse() {
+        Value::Integer(i)
+    } else {
+        Value::String(raw.to_owned())
+    }
+}
+
+fn exit(e: ConfigError) -> ! {
+    Cli::command()
+        .error(clap::error::ErrorKind::InvalidValue, e)
+        .exit()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cli::{Command, ConfigCommand};
+
+    const CONFIG: &str = indoc::indoc! {r#"
+        download-jobs = 2
+        lang = "en"
+        exclude-ext = ["m4b", "opus"]
+
+        [profile.car]
+        lang = "de"
+        deny-nolrc = true
+    "#};
+
+    #[test]
+    fn test_settings() {
+        let config: Config = CONFIG.parse().unwrap();
+
+        let defaults = config.settings(None).unwrap();
+        assert_eq!(Some(&Value::String("en".to_owned())), defaults.get("lang"));
+        assert_eq!(None, defaults.get("deny-nolrc"));
+
+        let car = config.settings(Some("car")).unwrap();
+        assert_eq!(Some(&Value::String("de".to_owned())), car.get("lang"));
+        assert_eq!(Some(&Value::Boolean(true)), car.get("deny-nolrc"));
+        assert_eq!(Some(&Value::Integer(2)), car.get("download-jobs"));
+
+        assert!(matches!(
+            config.settings(Some("nas")),
+            Err(ConfigError::UnknownProfile(_)),
+        ));
+    }
+
+    #[test]
+    fn test_values() {
+        let config: Config = CONFIG.parse().unwrap();
+        let settings = config.settings(None).unwrap();
+
+        assert_eq!(
+            Some(vec!["2".to_owned()]),
+            values(&settings["download-jobs"])
+        );
+        assert_eq!(
+            Some(vec!["m4b".to_owned(), "opus".to_owned()]),
+            values(&settings["exclude-ext"]),
+        );
+        assert_eq!(None, values(&Value::Table(Table::new())));
+    }
+
+    /// Parses `args` with the config at `config`, so the one of the user isn't read
+    fn parse_with(config: &Path, args: &[&str]) -> (Cli, Settings) {
+        let head = ["cnova", "--config"].map(OsString::from).into_iter();
+        let head = head.chain([config.as_os_str().to_owned()]);
+        parse(head.chain(args.iter().map(OsString::from)))
+    }
+
+    #[test]
+    fn test_flags() {
+        let config = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(config.path(), "deny-nolrc = true").unwrap();
+        let parse = |flags: &[&str]| {
+            let args: Vec<_> = flags.iter().copied().chain(["music"]).collect();
+            parse_with(config.path(), &args).0
+        };
+
+        assert!(parse(&[]).deny_nolrc);
+        assert!(!parse(&["--deny-nolrc=false"]).deny_nolrc);
+        assert!(parse(&["--deny-nolrc"]).deny_nolrc);
+        assert!(!parse(&[]).dry_run);
+        assert!(parse(&["--dry-run"]).dry_run);
+    }
+
+    #[test]
+    fn test_watch_options() {
+        let config = tempfile::NamedTempFile::new().unwrap();
+
+        for args in [
+            &["watch", "--download-jobs", "2", "--lang", "en", "music"][..],
+            &["--download-jobs", "2", "--lang", "en", "watch", "music"][..],
+        ] {
+            let (cli, _) = parse_with(config.path(), args);
+            assert!(
+                matches!(cli.command, Some(Command::Watch { .. })),
+                "{:?}",
+                args
+            );
+            assert_eq!(2, cli.download_jobs, "{:?}", args);
+            assert_eq!(Some("en"), cli.lang.as_deref(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_config_show() {
+        let config = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(config.path(), CONFIG).unwrap();
+
+        for args in [
+            &["--profile", "car", "config", "show"][..],
+            &["config", "show", "--profile", "car"][..],
+        ] {
+            let (cli, _) = parse_with(config.path(), args);
+            assert!(
+                matches!(
+                    cli.command,
+                    Some(Command::Config {
+                        command: ConfigCommand::Show
+                    })
+                ),
+                "{:?}",
+                args
+            );
+            assert_eq!(Some("de"), cli.lang.as_deref(), "{:?}", args);
+            assert!(cli.deny_nolrc, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        assert!(matches!(
+            "profile = 1".parse::<Config>(),
+            Err(ConfigError::Profiles),
+        ));
+        assert!(matches!(
+            "[profile]\ncar = 1".parse::<Config>(),
+            Err(ConfigError::Profiles),
+        ));
+    }
+}
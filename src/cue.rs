@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+/// Frames per second of CUE timestamps
+const FRAMES_PER_SEC: u64 = 75;
+
+/// A parsed CUE sheet, describing tracks of one or more audio files. Only the commands needed to
+/// request lyrics are kept, everything else is ignored
+#[derive(Debug, Default, PartialEq)]
+pub struct CueSheet {
+    pub performer: Option<String>,
+    pub title: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CueFile {
+    /// Name of the audio file, relative to the CUE sheet
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub performer: Option<String>,
+    pub title: Option<String>,
+    /// Position of `INDEX 01` in the audio file
+    pub start: Option<Duration>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum CueError {
+    #[error("line {0}: {1} outside of FILE")]
+    OutsideFile(usize, &'static str),
+    #[error("line {0}: invalid track number")]
+    TrackNumber(usize),
+    #[error("line {0}: invalid index timestamp")]
+    Timestamp(usize),
+}
+
+impl CueSheet {
+    pub fn parse(s: &str) -> Result<Self, CueError> {
+        let mut sheet = Self::default();
+
+        for (i, line) in s.trim_start_matches('\u{feff}').lines().enumerate() {
+            let line_number = i + 1;
+            let (command, args) = line
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or((line.trim(), ""));
+            let args = args.trim();
+
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => sheet.files.push(CueFile {
+                    name: file_name(args),
+                    tracks: Vec::new(),
+                }),
+                "TRACK" => {
+                    let file = sheet
+                        .files
+                        .last_mut()
+                        .ok_or(CueError::OutsideFile(line_number, "TRACK"))?;
+                    let number = args
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or(CueError::TrackNumber(line_number))?;
+                    file.tracks.push(CueTrack {
+                        number,
+                        performer: None,
+                        title: None,
+                        start: None,
+                    });
+                }
+                "INDEX" => {
+                    let track = sheet
+                        .files
+                        .last_mut()
+                        .and_then(|file| file.tracks.last_mut())
+                        .ok_or(CueError::OutsideFile(line_number, "INDEX"))?;
+                    let mut args = args.split_whitespace();
+                    if args.next().and_then(|n| n.parse::<u32>().ok()) == Some(1) {
+                        let timestamp = args
+                            .next()
+                            .and_then(timestamp)
+                            .ok_or(CueError::Timestamp(line_number))?;
+                        track.start = Some(timestamp);
+                    }
+                }
+                command @ ("TITLE" | "PERFORMER") => {
+                    let value = Some(unquote(args).to_owned()).filter(|v| !v.is_empty());
+                    let track = sheet
+                        .files
+                        .last_mut()
+                        .and_then(|file| file.tracks.last_mut());
+                    match (command, track) {
+                        ("TITLE", Some(track)) => track.title = value,
+                        ("PERFORMER", Some(track)) => track.performer = value,
+                        ("TITLE", None) => sheet.title = value,
+                        _ => sheet.performer = value,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /// Tracks of the audio file named `name`, ignoring case
+    pub fn tracks_of(&self, name: &str) -> Option<&[CueTrack]> {
+        self.files
+            .iter()
+            .find(|file| file.name.to_lowercase() == name.to_lowercase())
+            .map(|file| file.tracks.as_slice())
+    }
+}
+
+impl CueTrack {
+    /// Duration of this track, given the track that follows it in the same file. The last track
+    /// lasts until the end of the file, which is `total` long
+    pub fn duration(&self, next: Option<&CueTrack>, total: Option<Duration>) -> Option<Duration> {
+        let end = match next {
+            Some(next) => next.start?,
+            None => total?,
+        };
+        end.checked_sub(self.start?)
+    }
+}
+
+/// Extracts the file name from `FILE "name" TYPE` arguments
+fn file_name(args: &str) -> String {
+    let name = match args.strip_prefix('"') {
+        Some(rest) => rest.split_once('"').map_or(rest, |(name, _)| name),
+        None => args
+            .rsplit_once(char::is_whitespace)
+            .map_or(args, |(name, _)| name),
+    };
+    name.to_owned()
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// Parses an `mm:ss:ff` timestamp, where `ff` are frames
+fn timestamp(s: &str) -> Option<Duration> {
+    let mut parts = s.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    if seconds >= 60 || frames >= FRAMES_PER_SEC {
+        return None;
+    }
+    let millis = (minutes * 60 + seconds) * 1000 + frames * 1000 / FRAMES_PER_SEC;
+    Some(Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHEET: &str = indoc::indoc! {r#"
+        REM GENRE Rock
+        PERFORMER "Artist"
+        TITLE "Album"
+        FILE "Album.flac" WAVE
+          TRACK 01 AUDIO
+            TITLE "First"
+            INDEX 01 00:00:00
+          TRACK 02 AUDIO
+            TITLE "Second"
+            PERFORMER "Guest"
+            INDEX 00 03:10:00
+            INDEX 01 03:12:37
+    "#};
+
+    #[test]
+    fn test_parse() {
+        let sheet = CueSheet::parse(SHEET).unwrap();
+
+        assert_eq!(Some("Artist"), sheet.performer.as_deref());
+        assert_eq!(Some("Album"), sheet.title.as_deref());
+        let tracks = sheet.tracks_of("album.FLAC").unwrap();
+        assert_eq!(2, tracks.len());
+        assert_eq!(Some("First"), tracks[0].title.as_deref());
+        assert_eq!(None, tracks[0].performer);
+        assert_eq!(Some(Duration::ZERO), tracks[0].start);
+        assert_eq!(2, tracks[1].number);
+        assert_eq!(Some("Guest"), tracks[1].performer.as_deref());
+        assert_eq!(Some(Duration::from_millis(192_493)), tracks[1].start);
+    }
+
+    #[test]
+    fn test_duration() {
+        let sheet = CueSheet::parse(SHEET).unwrap();
+        let tracks = sheet.tracks_of("Album.flac").unwrap();
+
+        assert_eq!(
+            Some(Duration::from_millis(192_493)),
+            tracks[0].duration(tracks.get(1), None),
+        );
+        assert_eq!(
+            Some(Duration::from_millis(7_507)),
+            tracks[1].duration(None, Some(Duration::from_secs(200))),
+        );
+        assert_eq!(None, tracks[1].duration(None, None));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            Err(CueError::OutsideFile(1, "TRACK")),
+            CueSheet::parse("TRACK 01 AUDIO"),
+        );
+        assert_eq!(
+            Err(CueError::Timestamp(3)),
+            CueSheet::parse("FILE \"a.flac\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:61:00"),
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use core::fmt;
+use globset::{GlobBuilder, GlobMatcher};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+use std::time::Duration;
+
+/// Predicate over the tags of a track, e.g.
+/// `genre != "Classical" && year >= 1990 && duration < 20m`
+///
+/// - Text fields are `artist`, `albumartist`, `album`, `title` and `genre`. They support `==` and
+///   `!=`, which ignore case, and `~`, which matches a glob, like `artist ~ "the *"`. Values have
+///   to be quoted
+/// - Number fields are `year` and `track`, and `duration` is compared with durations like `90s`,
+///   `3m30s` or `1h`. They support `==`, `!=`, `<`, `<=`, `>` and `>=`
+///
+/// Comparisons are combined with `&&`, `||`, `!` and parentheses. A comparison with a missing tag
+/// is false, except for `!=`, so that `genre != "Classical"` keeps tracks without a genre
+#[derive(Clone, Debug)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+/// Tag values of a single track, that filters are evaluated on
+#[derive(Debug, Default)]
+pub struct Values {
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub track: Option<u32>,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextField {
+    Artist,
+    AlbumArtist,
+    Album,
+    Title,
+    Genre,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NumberField {
+    Year,
+    Track,
+    Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Glob,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text {
+        field: TextField,
+        negate: bool,
+        value: String,
+    },
+    Glob {
+        field: TextField,
+        matcher: GlobMatcher,
+    },
+    Number {
+        field: NumberField,
+        op: Op,
+        /// Milliseconds for durations
+        value: u64,
+    },
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum FilterError {
+    #[error("unexpected end of expression")]
+    End,
+    #[error("unexpected \"{0}\"")]
+    Unexpected(String),
+    #[error("unterminated string")]
+    Unterminated,
+    #[error("unknown field \"{0}\"")]
+    UnknownField(String),
+    #[error("\"{field}\" doesn't support \"{op}\"")]
+    Op { field: String, op: &'static str },
+    #[error("invalid value for \"{field}\": {value}")]
+    Value { field: String, value: String },
+    #[error("invalid glob: {0}")]
+    Glob(String),
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter().peekable(),
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.next() {
+            return Err(FilterError::Unexpected(token.to_string()));
+        }
+
+        Ok(Self {
+            source: s.to_owned(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Filter {
+    /// Filter, that matches `field` against `glob`, ignoring case
+    pub fn glob(field: TextField, glob: &str) -> Result<Self, FilterError> {
+        Ok(Self {
+            source: glob.to_owned(),
+            expr: Expr::Glob {
+                field,
+                matcher: matcher(glob)?,
+            },
+        })
+    }
+
+    pub fn matches(&self, values: &Values) -> bool {
+        self.expr.eval(values)
+    }
+}
+
+impl Expr {
+    fn eval(&self, values: &Values) -> bool {
+        match self {
+            Self::And(a, b) => a.eval(values) && b.eval(values),
+            Self::Or(a, b) => a.eval(values) || b.eval(values),
+            Self::Not(a) => !a.eval(values),
+            Self::Text {
+                field,
+                negate,
+                value,
+            } => match values.text(*field) {
+                Some(actual) => (actual.to_lowercase() == value.to_lowercase()) != *negate,
+                None => *negate,
+            },
+            Self::Glob { field, matcher } => values
+                .text(*field)
+                .is_some_and(|actual| matcher.is_match(actual)),
+            Self::Number { field, op, value } => match values.number(*field) {
+                Some(actual) => match op {
+                    Op::Eq => actual == *value,
+                    Op::Ne => actual != *value,
+                    Op::Lt => actual < *value,
+                    Op::Le => actual <= *value,
+                    Op::Gt => actual > *value,
+                    Op::Ge => actual >= *value,
+                    Op::Glob => false,
+                },
+                None => *op == Op::Ne,
+            },
+        }
+    }
+}
+
+impl Values {
+    fn text(&self, field: TextField) -> Option<&str> {
+        let value = match field {
+            TextField::Artist => &self.artist,
+            TextField::AlbumArtist => &self.album_artist,
+            TextField::Album => &self.album,
+            TextField::Title => &self.title,
+            TextField::Genre => &self.genre,
+        };
+        value.as_deref()
+    }
+
+    fn number(&self, field: NumberField) -> Option<u64> {
+        match field {
+            NumberField::Year => self.year.map(u64::from),
+            NumberField::Track => self.track.map(u64::from),
+            NumberField::Duration => self.duration.map(|d| d.as_millis() as u64),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Field names and unquoted values, like numbers and durations
+    Word(String),
+    Str(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => f.write_str(word),
+            Self::Str(s) => write!(f, "{:?}", s),
+            Self::Op(op) => f.write_str(op.as_str()),
+            Self::And => f.write_str("&&"),
+            Self::Or => f.write_str("||"),
+            Self::Not => f.write_str("!"),
+            Self::Open => f.write_str("("),
+            Self::Close => f.write_str(")"),
+        }
+    }
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Glob => "~",
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, FilterError> {
+    let mut res = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if(|&(_, c)| c == expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Op(Op::Glob),
+            '=' if next_is('=') => Token::Op(Op::Eq),
+            '!' if next_is('=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if next_is('=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '"' => Token::Str(string(&mut chars)?),
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_') {
+                    end = j + c.len_utf8();
+                }
+                Token::Word(s[i..end].to_owned())
+            }
+            c => return Err(FilterError::Unexpected(c.to_string())),
+        };
+        res.push(token);
+    }
+
+    Ok(res)
+}
+
+/// Reads a string up to the closing quote. Quotes and backslashes can be escaped with a backslash
+fn string(chars: &mut Peekable<CharIndices<'_>>) -> Result<String, FilterError> {
+    let mut res = String::new();
+    loop {
+        match chars.next().ok_or(FilterError::Unterminated)? {
+            (_, '"') => return Ok(res),
+            (_, '\\') => res.push(chars.next().ok_or(FilterError::Unterminated)?.1),
+            (_, c) => res.push(c),
+        }
+    }
+}
+
+struct Parser<I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Token>> Parser<I> {
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut res = self.and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            res = Expr::Or(Box::new(res), Box::new(self.and()?));
+        }
+        Ok(res)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut res = self.unary()?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            res = Expr::And(Box::new(res), Box::new(self.unary()?));
+        }
+        Ok(res)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FilterError> {
+        match self.tokens.next().ok_or(FilterError::End)? {
+            Token::Not => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let res = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(res),
+                    Some(token) => Err(FilterError::Unexpected(token.to_string())),
+                    None => Err(FilterError::End),
+                }
+            }
+            Token::Word(field) => self.comparison(field),
+            token => Err(FilterError::Unexpected(token.to_string())),
+        }
+    }
+
+    fn comparison(&mut self, field: String) -> Result<Expr, FilterError> {
+        let op = match self.tokens.next().ok_or(FilterError::End)? {
+            Token::Op(op) => op,
+            token => return Err(FilterError::Unexpected(token.to_string())),
+        };
+        let value = self.tokens.next().ok_or(FilterError::End)?;
+        let invalid = |value: &Token| FilterError::Value {
+            field: field.clone(),
+            value: value.to_string(),
+        };
+
+        if let Some(text) = text_field(&field) {
+            let Token::Str(value) = value else {
+                return Err(invalid(&value));
+            };
+            return match op {
+                Op::Eq | Op::Ne => Ok(Expr::Text {
+                    field: text,
+                    negate: op == Op::Ne,
+                    value,
+                }),
+                Op::Glob => Ok(Expr::Glob {
+                    field: text,
+                    matcher: matcher(&value)?,
+                }),
+                op => Err(FilterError::Op {
+                    field,
+                    op: op.as_str(),
+                }),
+            };
+        }
+
+        let number =
+            number_field(&field).ok_or_else(|| FilterError::UnknownField(field.clone()))?;
+        if op == Op::Glob {
+            return Err(FilterError::Op {
+                field,
+                op: op.as_str(),
+            });
+        }
+        let parsed = match (&value, number) {
+            (Token::Word(word), NumberField::Duration) => duration(word),
+            (Token::Word(word), _) => word.parse().ok(),
+            _ => None,
+        };
+        Ok(Expr::Number {
+            field: number,
+            op,
+            value: parsed.ok_or_else(|| invalid(&value))?,
+        })
+    }
+}
+
+fn text_field(name: &str) -> Option<TextField> {
+    let field = match name {
+        "artist" => TextField::Artist,
+        "albumartist" => TextField::AlbumArtist,
+        "album" => TextField::Album,
+        "title" => TextField::Title,
+        "genre" => TextField::Genre,
+        _ => return None,
+    };
+    Some(field)
+}
+
+fn number_field(name: &str) -> Option<NumberField> {
+    let field = match name {
+        "year" => NumberField::Year,
+        "track" => NumberField::Track,
+        "duration" => NumberField::Duration,
+        _ => return None,
+    };
+    Some(field)
+}
+
+/// Milliseconds of a duration like `90`, `90s`, `3m30s` or `1h`. Plain numbers are seconds
+fn duration(s: &str) -> Option<u64> {
+    if let Ok(secs) = s.parse::<u64>() {
+        return secs.checked_mul(1000);
+    }
+
+    let mut res = 0u64;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'h' => 3_600_000,
+            'm' => 60_000,
+            's' => 1000,
+            _ => return None,
+        };
+        let value = std::mem::take(&mut number).parse::<u64>().ok()?;
+        res = res.checked_add(value.checked_mul(unit)?)?;
+    }
+
+    number.is_empty().then_some(res)
+}
+
+fn matcher(glob: &str) -> Result<GlobMatcher, FilterError> {
+    GlobBuilder::new(glob)
+        .case_insensitive(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| FilterError::Glob(e.kind().to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn values() -> Values {
+        Values {
+            artist: Some("The Beatles".to_owned()),
+            album: Some("Abbey Road".to_owned()),
+            title: Some("Come Together".to_owned()),
+            genre: Some("Rock".to_owned()),
+            year: Some(1969),
+            track: Some(1),
+            duration: Some(Duration::from_secs(259)),
+            ..Values::default()
+        }
+    }
+
+    fn matches(filter: &str) -> bool {
+        filter.parse::<Filter>().unwrap().matches(&values())
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(
+            r#"genre != "Classical" && year >= 1960 && duration < 20m"#
+        ));
+        assert!(matches(r#"genre == "rock""#));
+        assert!(matches(r#"artist ~ "the *""#));
+        assert!(matches(
+            r#"year < 1960 || (track == 1 && !(duration > 4m30s))"#
+        ));
+        assert!(!matches(r#"year < 1960 || duration >= 4m20s"#));
+        assert!(!matches(r#"title ~ "*live*""#));
+    }
+
+    #[test]
+    fn test_missing_values() {
+        assert!(matches(r#"albumartist != "Various Artists""#));
+        assert!(!matches(r#"albumartist == "Various Artists""#));
+        assert!(!matches(r#"albumartist ~ "*""#));
+
+        let empty = Values::default();
+        let filter: Filter = "year != 2000".parse().unwrap();
+        assert!(filter.matches(&empty));
+        let filter: Filter = "year >= 2000".parse().unwrap();
+        assert!(!filter.matches(&empty));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = |s: &str| s.parse::<Filter>().unwrap_err();
+
+        assert_eq!(FilterError::End, err("genre =="));
+        assert_eq!(
+            FilterError::UnknownField("bpm".to_owned()),
+            err("bpm > 120")
+        );
+        assert_eq!(
+            FilterError::Op {
+                field: "artist".to_owned(),
+                op: "<"
+            },
+            err(r#"artist < "b""#),
+        );
+        assert!(matches!(err("year == \"1990\""), FilterError::Value { .. }));
+        assert!(matches!(err("duration < 5x"), FilterError::Value { .. }));
+        assert_eq!(FilterError::Unterminated, err("genre == \"Rock"));
+        assert_eq!(FilterError::Unexpected(")".to_owned()), err("year == 1 )"));
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(Some(90_000), duration("90"));
+        assert_eq!(Some(210_000), duration("3m30s"));
+        assert_eq!(Some(3_600_000), duration("1h"));
+        assert_eq!(None, duration("3m30"));
+        assert_eq!(None, duration("m"));
+    }
+}
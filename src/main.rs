@@ -1,38 +1,105 @@
 mod cli;
+mod config;
+mod cue;
+mod filter;
 mod net;
 mod op;
+mod pattern;
+mod playlist;
 mod remote;
+mod rewrite;
+mod template;
 mod trace;
 
-use clap::Parser as _;
+use std::process::ExitCode;
+use std::time::Duration;
 use tracing::level_filters::LevelFilter;
 
-use crate::cli::Cli;
+use crate::cli::{Cli, Command, ConfigCommand};
 use net::RemoteImpl;
+use op::journal::UndoError;
+use op::summary::Status;
 
 const TRACING_SET_GLOBAL_DEFAULT_EXPECT_MSG: &str = "unable to set global tracing subscriber";
 
 #[tokio::main]
 #[tracing::instrument(level = "trace")]
-async fn main() {
+async fn main() -> ExitCode {
     if cfg!(debug_assertions) {
         let sub = tracing_subscriber::fmt()
             .with_max_level(LevelFilter::DEBUG)
+            .with_writer(op::progress::LogWriter::default)
             .finish();
         tracing::subscriber::set_global_default(sub).expect(TRACING_SET_GLOBAL_DEFAULT_EXPECT_MSG);
     } else {
         let sub = tracing_subscriber::fmt()
             .with_max_level(LevelFilter::INFO)
+            .with_writer(op::progress::LogWriter::default)
             .finish();
         tracing::subscriber::set_global_default(sub).expect(TRACING_SET_GLOBAL_DEFAULT_EXPECT_MSG);
     }
 
-    let mut cli = Cli::parse();
+    let (mut cli, settings) = config::parse(std::env::args_os());
 
-    let remote = Box::leak(Box::new(RemoteImpl::new(cli.proxy.take()) // not gonna need proxy anywhere else
+    match cli.command.take() {
+        Some(Command::Undo { run_id }) => {
+            let root = cli.journal_dir.or_else(op::journal::default_root).expect(
+                "couldn't determine journal directory, please specify it with --journal-dir",
+            );
+            return match op::journal::undo(&root, run_id.as_deref()).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    tracing::error!(%e, "failed to undo changes");
+                    match e {
+                        UndoError::Incomplete(_) => Status::Failed.into(),
+                        _ => Status::Fatal.into(),
+                    }
+                }
+            };
+        }
+        Some(Command::Clean { paths }) => {
+            return match op::clean::clean(&paths, cli).await {
+                Ok(status) => status.into(),
+                Err(e) => {
+                    tracing::error!(%e, "failed to clean up");
+                    Status::Failed.into()
+                }
+            };
+        }
+        Some(Command::Stats { paths, format }) => {
+            return match op::stats::stats(paths, format, cli).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    tracing::error!(%e, "failed to collect statistics");
+                    Status::Failed.into()
+                }
+            };
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Show,
+        }) => {
+            print!("{}", settings.show());
+            return ExitCode::SUCCESS;
+        }
+        Some(Command::Watch { paths, settle }) => {
+            let remote = remote(&mut cli);
+            if let Err(e) = op::watch::watch(remote, paths, Duration::from_secs(settle), cli).await
+            {
+                tracing::error!(%e, "stopped watching for new tracks");
+                return Status::Fatal.into();
+            }
+            return ExitCode::SUCCESS;
+        }
+        None => {}
+    }
+
+    let remote = remote(&mut cli);
+    op::start_up(remote, cli).await.status().into()
+}
+
+fn remote(cli: &mut Cli) -> &'static RemoteImpl {
+    Box::leak(Box::new(RemoteImpl::new(cli.proxy.take()) // not gonna need proxy anywhere else
         .expect(
             "couldn't build remote. this means that we can't execute requests. are all the parameters verified at the cli level?"
-        )));
-
-    op::start_up(remote, cli).await;
+        )))
 }
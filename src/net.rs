@@ -7,6 +7,7 @@ mod url {
 
     const BASE: &str = "https://lrclib.net/api/";
     pub const GET: &str = concatcp!(BASE, "get");
+    pub const GET_BY_ID: &str = concatcp!(BASE, "get/");
 }
 
 pub struct RemoteImpl {
@@ -24,19 +25,8 @@ impl RemoteImpl {
 
         builder.build().map(|client| Self { client })
     }
-}
-
-impl Remote for RemoteImpl {
-    #[tracing::instrument(level = "trace", skip(self))]
-    async fn get_lyrics(&self, req: &LyricsRequest) -> remote::Result {
-        tracing::trace!("building request");
-        let request = self
-            .client
-            .get(url::GET)
-            .query(req)
-            .build()
-            .map_err(LyricsError::InvalidRequest)?;
 
+    async fn execute(&self, request: reqwest::Request, url: &'static str) -> remote::Result {
         tracing::trace!("requesting the value");
         self.client
             .execute(request)
@@ -47,10 +37,7 @@ impl Remote for RemoteImpl {
                 if status.is_success() {
                     Ok(response)
                 } else {
-                    Err(LyricsError::InvalidStatusCode {
-                        status,
-                        url: url::GET,
-                    })
+                    Err(LyricsError::InvalidStatusCode { status, url })
                 }
             })?
             .json()
@@ -58,3 +45,30 @@ impl Remote for RemoteImpl {
             .map_err(|e| e.into())
     }
 }
+
+impl Remote for RemoteImpl {
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn get_lyrics(&self, req: &LyricsRequest) -> remote::Result {
+        tracing::trace!("building request");
+        let request = self
+            .client
+            .get(url::GET)
+            .query(req)
+            .build()
+            .map_err(LyricsError::InvalidRequest)?;
+
+        self.execute(request, url::GET).await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn get_lyrics_by_id(&self, id: u64) -> remote::Result {
+        tracing::trace!("building request");
+        let request = self
+            .client
+            .get(format!("{}{}", url::GET_BY_ID, id))
+            .build()
+            .map_err(LyricsError::InvalidRequest)?;
+
+        self.execute(request, url::GET_BY_ID).await
+    }
+}
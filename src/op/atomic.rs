@@ -0,0 +1,100 @@
+use std::ffi::OsStr;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use tempfile::{NamedTempFile, TempPath};
+
+/// Prefix of temporary files
+const PREFIX: &str = ".cnova-";
+/// Suffix of staged files, see [`stage`]
+const STAGED: &str = ".tmp";
+/// Suffix of files moved out of the way, see [`set_aside`]
+const ASIDE: &str = ".old";
+
+/// Writes `contents` to a temporary file in the same directory as `path` and flushes it to the
+/// disk. Nothing appears at `path` itself until [`commit`] is called, and the temporary file is
+/// deleted if the result is dropped before that
+pub async fn stage(path: &Path, contents: Vec<u8>) -> io::Result<NamedTempFile> {
+    let dir = parent(path);
+    blocking(move || {
+        let mut tmp = tempfile::Builder::new()
+            .prefix(PREFIX)
+            .suffix(STAGED)
+            .tempfile_in(dir)?;
+        tmp.write_all(&contents)?;
+        tmp.as_file().sync_all()?;
+        Ok(tmp)
+    })
+    .await
+}
+
+/// Atomically moves `tmp` to `path`, replacing anything that was there before
+pub async fn commit(tmp: NamedTempFile, path: &Path) -> io::Result<()> {
+    let path = path.to_owned();
+    blocking(move || {
+        tmp.persist(&path).map_err(|e| e.error)?;
+        sync_dir(&parent(&path))
+    })
+    .await
+}
+
+/// Moves the file at `path` out of the way, to a temporary file in the same directory.
+/// Returns [`None`] if there's nothing at `path`. Dropping the result deletes the file for
+/// good, while [`restore`] moves it back
+pub async fn set_aside(path: &Path) -> io::Result<Option<TempPath>> {
+    let path = path.to_owned();
+    blocking(move || {
+        let aside = tempfile::Builder::new()
+            .prefix(PREFIX)
+            .suffix(ASIDE)
+            .tempfile_in(parent(&path))?
+            .into_temp_path();
+
+        match std::fs::rename(&path, &aside) {
+            Ok(()) => Ok(Some(aside)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    })
+    .await
+}
+
+/// Moves a file previously moved by [`set_aside`] back to `path`
+pub async fn restore(aside: TempPath, path: &Path) -> io::Result<()> {
+    let path = path.to_owned();
+    blocking(move || aside.persist(&path).map_err(|e| e.error)).await
+}
+
+/// Whether a file named `name` is a temporary file, that's left behind, if the process is killed
+/// in the middle of a write
+pub fn is_leftover(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name.starts_with(PREFIX) && (name.ends_with(STAGED) || name.ends_with(ASIDE))
+}
+
+fn parent(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Makes sure that renames in `dir` survive a crash
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+async fn blocking<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .expect(super::JOIN_HANDLE_EXPECT_MSG)
+}
@@ -0,0 +1,273 @@
+use super::atomic;
+use super::file::{self, NoPathsError};
+use super::journal::{Entry, Journal};
+use super::listing::Listings;
+use super::overrides::Overrides;
+use super::plan::{self, Action};
+use super::sheets::CueSheets;
+use super::summary::Status;
+use crate::cli::Cli;
+use crate::template::Fields;
+use ignore::WalkState;
+use lofty::probe::Probe;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const ORPHAN_REASON: &str = "track doesn't exist";
+const LEFTOVER_REASON: &str = "left behind by an interrupted write";
+
+/// Deletes `.lrc` and `.nolrc` files under `paths`, whose tracks don't exist anymore, along with
+/// temporary files of interrupted writes. Only reports them, if `--dry-run` is specified. The run
+/// fails, if any of the files or directories couldn't be handled
+#[tracing::instrument(level = "trace", skip(cli))]
+pub async fn clean(paths: &[PathBuf], cli: Cli) -> Result<Status, NoPathsError> {
+    let walk = file::walk_builder(paths, &cli)?.build_parallel();
+
+    let (orphans, walk_failed, cli) = tokio::task::spawn_blocking(move || {
+        let orphans = Mutex::new(Vec::new());
+        let failed = AtomicBool::new(false);
+        let caches = Caches::default();
+        walk.run(|| {
+            let orphans = &orphans;
+            let failed = &failed;
+            let caches = &caches;
+            let cli = &cli;
+            Box::new(move |entry| {
+                let found = |path, reason| {
+                    orphans
+                        .lock()
+                        .expect("walker thread panicked while holding the lock")
+                        .push((path, reason));
+                };
+                match entry {
+                    Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_dir()) => {
+                        // Temporary files are hidden, so the walker doesn't see them by itself
+                        let dir = entry.path();
+                        caches
+                            .listings
+                            .get(dir)
+                            .entries()
+                            .filter(|name| atomic::is_leftover(name))
+                            .for_each(|name| found(dir.join(name), LEFTOVER_REASON));
+                    }
+                    Ok(entry) if is_orphan(entry.path(), cli, caches) => {
+                        found(entry.into_path(), ORPHAN_REASON);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!(%e);
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                }
+
+                WalkState::Continue
+            })
+        });
+
+        let orphans = orphans
+            .into_inner()
+            .expect("walker thread panicked while holding the lock");
+        (orphans, failed.into_inner(), cli)
+    })
+    .await
+    .expect(super::JOIN_HANDLE_EXPECT_MSG);
+
+    let journal = super::open_journal(&cli);
+    let mut failed = 0;
+    for (path, reason) in &orphans {
+        if cli.dry_run {
+            plan::report(Action::Delete(path), reason);
+            continue;
+        }
+
+        match delete(path, journal.as_deref()).await {
+            Ok(()) => tracing::info!(path = %path.display(), reason, "deleted file"),
+            Err(e) => {
+                tracing::warn!(%e, path = %path.display(), reason, "failed to delete file");
+                failed += 1;
+            }
+        }
+    }
+    super::report_journal(journal.as_deref()).await;
+
+    if failed > 0 {
+        tracing::error!(failed, "some files couldn't be deleted");
+    }
+    if failed > 0 || walk_failed {
+        Ok(Status::Failed)
+    } else {
+        Ok(Status::Ok)
+    }
+}
+
+/// Per-directory state, shared by all the walker threads
+#[derive(Debug, Default)]
+struct Caches {
+    listings: Listings,
+    overrides: Overrides,
+    sheets: CueSheets,
+    tracks: Mutex<HashMap<PathBuf, Arc<DirTracks>>>,
+}
+
+/// Tracks of a single directory, along with the names of their lyrics files. All the names are
+/// lowercased
+#[derive(Debug, Default)]
+struct DirTracks {
+    names: Vec<String>,
+    /// Names of lyrics files of CUE tracks without the language and the extension, e.g. `album.01`
+    cue_stems: HashSet<String>,
+    claimed: HashSet<String>,
+}
+
+impl Caches {
+    /// Tracks in `dir`. Each directory is looked at only once, the first time a lyrics file in it
+    /// is checked
+    fn tracks(&self, dir: &Path, cli: &Cli) -> Arc<DirTracks> {
+        let lock = || {
+            self.tracks
+                .lock()
+                .expect("walker thread panicked while holding the lock")
+        };
+        if let Some(tracks) = lock().get(dir) {
+            return tracks.clone();
+        }
+
+        // Probing files and reading tags takes a while, so the lock isn't held meanwhile
+        let tracks = Arc::new(DirTracks::read(dir, cli, self));
+        lock().entry(dir.to_owned()).or_insert(tracks).clone()
+    }
+}
+
+impl DirTracks {
+    /// Looks for tracks in `dir` and names their lyrics files the way [`file::find_lrc`] and
+    /// [`file::find_nolrc`] do. Tags are only read, if the names depend on them
+    fn read(dir: &Path, cli: &Cli, caches: &Caches) -> Self {
+        let needs_tags = file::names_need_tags(cli);
+        let listing = caches.listings.get(dir);
+
+        let mut tracks = Self::default();
+        for path in listing.entries().map(|name| dir.join(name)) {
+            if !is_track(&path) {
+                continue;
+            }
+
+            let mut tags = file::Tags::default();
+            if needs_tags {
+                match file::deep_inspect(&path) {
+                    Ok(tagged_file) => tags = file::read_tags(&tagged_file, &path, cli),
+                    Err(e) => tracing::debug!(%e, path = %path.display(), "failed to read tags"),
+                }
+                if let Ok(over) = caches.overrides.get(&path, &caches.listings) {
+                    tags.apply_override(&over);
+                }
+            }
+
+            let fields = Fields {
+                artist: tags.artist.as_deref(),
+                title: tags.title.as_deref(),
+                track: tags.track,
+            };
+            tracks.claimed.extend(
+                file::lyrics_paths(&path, &fields, cli)
+                    .filter_map(|lyrics| lyrics.file_name().map(key)),
+            );
+            tracks.names.extend(path.file_name().map(key));
+
+            if cli.no_cue {
+                continue;
+            }
+            if let Some(sheet) = caches.sheets.find(&path, &caches.listings) {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let numbers = sheet.tracks_of(&name).unwrap_or_default().iter();
+                tracks
+                    .cue_stems
+                    .extend(numbers.map(|track| key(&file::cue_track_stem(&path, track.number))));
+            }
+        }
+
+        tracks
+    }
+}
+
+/// Whether `path` is a lyrics file, that doesn't belong to any track. A lyrics file belongs to
+/// a track, if it's named as `--lrc-name` or `--alt-lrc-name` would name the lyrics of the track.
+/// To not delete lyrics named by other tools, it also belongs to a track, if its name without the
+/// extension matches either the track stem, or the full name of the track, optionally followed by
+/// a language suffix, ignoring case. For example, `song.lrc`, `song.flac.lrc` and `song.en.lrc`
+/// all belong to `song.flac`. Lyrics of CUE tracks, like `album.01.lrc` or `album.01.en.lrc`,
+/// belong to the file split by the sheet
+fn is_orphan(path: &Path, cli: &Cli, caches: &Caches) -> bool {
+    if !file::is_lyrics(path) || !path.is_file() {
+        return false;
+    }
+
+    let (Some(dir), Some(name), Some(base)) = (path.parent(), path.file_name(), path.file_stem())
+    else {
+        return false;
+    };
+    let base = base.to_string_lossy().to_lowercase();
+    let base_stem = Path::new(&base).file_stem().and_then(OsStr::to_str);
+
+    let tracks = caches.tracks(dir, cli);
+    let has_track = tracks.names.iter().any(|name| {
+        let name = Path::new(name);
+        let matches = |candidate: &str| {
+            name.file_stem().is_some_and(|stem| stem == candidate) || name == Path::new(candidate)
+        };
+
+        matches(&base) || base_stem.is_some_and(matches)
+    });
+    let is_cue_track = tracks.cue_stems.contains(&base)
+        || base_stem.is_some_and(|stem| tracks.cue_stems.contains(stem));
+
+    !has_track && !is_cue_track && !tracks.claimed.contains(&key(name))
+}
+
+/// Whether the file at `path` is a track. Unlike in the main run, this doesn't depend on
+/// `--include-ext` and `--exclude-ext`, so lyrics of tracks, that are left out, aren't deleted.
+/// Files with extensions unknown to lofty are probed by their contents
+fn is_track(path: &Path) -> bool {
+    let Some(ext) = path.extension() else {
+        return false;
+    };
+    if lofty::file::EXTENSIONS
+        .iter()
+        .any(|e| ext.eq_ignore_ascii_case(e))
+    {
+        return true;
+    }
+    if file::is_lyrics(path) || !path.is_file() {
+        return false;
+    }
+
+    Probe::open(path)
+        .ok()
+        .and_then(|probe| probe.guess_file_type().ok())
+        .is_some_and(|probe| probe.file_type().is_some())
+}
+
+fn key(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
+
+async fn delete(path: &Path, journal: Option<&Journal>) -> std::io::Result<()> {
+    let backup = match journal {
+        Some(journal) => journal.backup(path).await?,
+        None => None,
+    };
+
+    tokio::fs::remove_file(path).await?;
+
+    if let (Some(journal), Some(backup)) = (journal, backup) {
+        let entry = Entry::Delete {
+            path: path.to_owned(),
+            backup,
+        };
+        super::record(journal, &entry).await;
+    }
+
+    Ok(())
+}
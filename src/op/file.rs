@@ -1,18 +1,39 @@
+use super::guess::{self, Instrumental};
+use super::index::{self, Index, Stamp};
+use super::listing::Listings;
+use super::marker::NoLyrics;
+use super::outcome::Outcome;
+use super::output::{self, Output};
+use super::overrides::{Override, OverrideError, Overrides};
+use super::progress::Progress;
+use super::prune::Prune;
+use super::sheets::CueSheets;
 use crate::{
-    cli::{Cli, FileMatchStrictness, LrcAcquireBehavior},
+    cli::{AudioExts, Cli, FileMatchStrictness, LrcAcquireBehavior},
+    cue::{CueSheet, CueTrack},
+    filter::Values,
+    pattern::PathPattern,
+    playlist,
     remote::LyricsRequest,
+    rewrite::{Field, Rewrites},
+    template::{Fields, LrcTemplate},
     trace::TraceExt as _,
 };
 use ignore::WalkState;
 use lofty::{
     error::LoftyError,
-    file::{TaggedFile, TaggedFileExt},
+    file::{AudioFile as _, FileType, TaggedFile, TaggedFileExt},
     probe::Probe,
     read_from_path,
-    tag::Accessor,
+    tag::{Accessor, ItemKey},
 };
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead};
+use std::iter;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::{fmt::Debug, path::PathBuf};
-use std::{io, path::Path};
 
 #[derive(Debug, thiserror::Error)]
 pub enum PackError {
@@ -31,19 +52,133 @@ pub enum PackError {
     },
     #[error(transparent)]
     Ignore(#[from] ignore::Error),
+    #[error(
+        "\"{}\" resolves to the same lyrics file \"{}\" as {:?}, skipping",
+        path.display(),
+        target.display(),
+        others,
+    )]
+    Collision {
+        path: PathBuf,
+        target: PathBuf,
+        others: Vec<PathBuf>,
+    },
+    #[error("failed to read overrides from \"{}\": {source}", path.display())]
+    Override {
+        path: PathBuf,
+        source: Arc<OverrideError>,
+    },
+    #[error("\"{}\": {source}", path.display())]
+    Track {
+        path: PathBuf,
+        source: Box<PackError>,
+    },
     // TODO (errors): add file match error
 }
 
-pub type PackResult = Result<(LyricsRequest, PathBuf), PackError>;
+impl PackError {
+    /// Track, that failed, if it's known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Collision { path, .. } | Self::Track { path, .. } => Some(path),
+            Self::Override { .. } | Self::Lofty(_) | Self::Io(_) => None,
+            Self::RequestPrepare { .. } | Self::Ignore(_) => None,
+        }
+    }
+}
+
+/// Everything needed to fetch lyrics for a single track
+#[derive(Debug)]
+pub struct Pack {
+    pub request: LyricsRequest,
+    /// Individual artists of a track with multiple artists, tried one by one, if there are no
+    /// lyrics for [`LyricsRequest::artist`]
+    pub alt_artists: Vec<String>,
+    /// LRCLIB id of the lyrics, pinned by overrides. Takes precedence over the request
+    pub lrclib_id: Option<u64>,
+    /// The track is known to be instrumental, so lyrics aren't requested
+    pub instrumental: Option<Instrumental>,
+    /// Path to the track itself
+    pub path: PathBuf,
+    pub sidecar: Sidecar,
+    /// Size and modification time of the track, when it was read. [`None`] for tracks, that
+    /// aren't indexed
+    pub stamp: Option<Stamp>,
+}
+
+/// Location of lyrics files for a single track
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sidecar {
+    /// Full path to lyrics files, without an extension
+    base: PathBuf,
+}
+
+impl Sidecar {
+    pub fn new<P>(base: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self { base: base.into() }
+    }
+
+    pub fn for_track(path: &Path, fields: &Fields, cli: &Cli) -> Self {
+        let name = cli.lrc_name.expand(path, fields, cli.lang.as_deref());
+        Self::new(path.with_file_name(name))
+    }
+
+    /// Lyrics files of a single CUE track of the file at `path`, e.g. `album.01` or `album.01.en`
+    pub fn for_cue_track(path: &Path, number: u32, cli: &Cli) -> Self {
+        let mut name = cue_track_stem(path, number);
+        if let Some(lang) = &cli.lang {
+            name.push(".");
+            name.push(lang);
+        }
+        Self::new(path.with_file_name(name))
+    }
+
+    pub fn lrc(&self) -> PathBuf {
+        self.with_ext("lrc")
+    }
+
+    pub fn nolrc(&self) -> PathBuf {
+        self.with_ext("nolrc")
+    }
+
+    /// Appends `ext` to the base path. Unlike [`Path::with_extension`], doesn't replace anything
+    /// that looks like an extension already, e.g. `song.flac` becomes `song.flac.lrc`
+    fn with_ext(&self, ext: &str) -> PathBuf {
+        let mut res = self.base.clone().into_os_string();
+        res.push(".");
+        res.push(ext);
+        res.into()
+    }
+}
+
+/// Name of the lyrics files of a single CUE track of the file at `path`, without the language
+/// and the extension, e.g. `album.01`
+pub fn cue_track_stem(path: &Path, number: u32) -> OsString {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!(".{:02}", number));
+    name
+}
+
+pub type PackResult = Result<Pack, PackError>;
 pub type PacksRx = tokio::sync::mpsc::UnboundedReceiver<PackResult>;
 pub type PacksTx = tokio::sync::mpsc::UnboundedSender<PackResult>;
 
+/// Name of ignore files, that only apply to this program
+const IGNORE_FILE_NAME: &str = ".cnovaignore";
+
+/// None of the paths were left to go through, after empty playlists were expanded
 #[derive(Debug, thiserror::Error)]
-#[error("no paths were provided")]
+#[error("no paths to go through, given playlists are empty")]
 pub struct NoPathsError;
 
-pub fn prepare_entries(tx: &PacksTx, cli: &Cli) -> Result<(), NoPathsError> {
-    let mut iter = cli.paths.iter();
+/// Walker over all the `paths`, configured according to `cli`. Every command that goes
+/// through the library has to use this, so that they all see the same files
+pub fn walk_builder(paths: &[PathBuf], cli: &Cli) -> Result<ignore::WalkBuilder, NoPathsError> {
+    let paths = playlist::expand(paths);
+    let mut iter = paths.iter();
 
     let mut builder = ignore::WalkBuilder::new(iter.next().ok_or(NoPathsError)?);
 
@@ -51,72 +186,623 @@ pub fn prepare_entries(tx: &PacksTx, cli: &Cli) -> Result<(), NoPathsError> {
         builder.add(path);
     }
 
-    let walk = builder
-        .ignore_case_insensitive(true) // TODO (config): put this into config, maybe?
+    builder
+        .ignore_case_insensitive(!cli.case_sensitive_ignore)
         .ignore(true)
-        .git_ignore(false) // TODO (config): is this even needed in context of music?
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .git_ignore(cli.git_ignore)
         .git_global(false)
         .git_exclude(false)
-        .require_git(true)
+        .require_git(false)
         .follow_links(!cli.no_follow_symlinks)
         .hidden(!cli.no_ignore_hidden)
         .threads(cli.traversal_jobs.into())
-        .build_parallel();
+        .max_depth(cli.walk.max_depth)
+        .max_filesize(cli.walk.max_filesize);
+
+    let prune = Prune::new(&cli.walk);
+    let (strictness, exts) = (cli.strictness, cli.exts.clone());
+    builder.filter_entry(move |entry| {
+        let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+        // Globs pick tracks, so lyrics files and other files are left for the commands to handle
+        if !is_dir
+            && (is_lyrics(entry.path()) || !could_be_track(entry.file_name(), strictness, &exts))
+        {
+            return true;
+        }
+        prune.keeps(entry.path(), is_dir)
+    });
+
+    Ok(builder)
+}
 
-    walk.run(move || {
+pub fn prepare_entries(tx: &PacksTx, cli: &Cli, caches: Caches) -> Result<(), NoPathsError> {
+    let walk = walk_builder(&cli.paths, cli)?.build_parallel();
+
+    // Packs aren't sent right away, so that tracks sharing the same lyrics file
+    // can be detected before anything is written
+    let pending = Pending::default();
+    walk.run(|| {
         let tx = tx.clone();
+        let pending = &pending;
+        let caches = &caches;
         Box::new(move |entry| {
-            if let Some(res) = entry
-                .map_err(|e| e.into())
-                .and_then(|entry| from_entry(entry.path(), cli)).transpose() {
-                    tracing::trace!(?res, "sending result over");
-                    tx.send(res).expect("this channel is unbounded, and, therefore, should always be available to send to");
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    send(&tx, Err(e.into()));
+                    return WalkState::Continue;
                 }
+            };
+            let path = entry.path();
+            if !pending.visit(path) {
+                tracing::debug!(path = %path.display(), "entry was already seen, skipping");
+                return WalkState::Continue;
+            }
+
+            let packs = from_entry(path, cli, caches).unwrap_or_else(|e| {
+                send(&tx, Err(e));
+                Vec::new()
+            });
+            pending.scanned(&tx, path, packs, cli, &caches.listings);
 
             WalkState::Continue
         })
     });
 
+    pending.finish(tx);
+
     Ok(())
 }
 
-#[tracing::instrument(level = "trace")]
-fn from_entry(path: &Path, cli: &Cli) -> Result<Option<(LyricsRequest, PathBuf)>, PackError> {
+/// Packs of directories, that aren't fully scanned yet. Lyrics files are always next to their
+/// tracks, so a track can only collide with others from the same directory. Packs of a directory
+/// are sent, as soon as all of its files, that could be tracks, are scanned
+#[derive(Debug, Default)]
+pub struct Pending {
+    dirs: Mutex<HashMap<PathBuf, PendingDir>>,
+    /// Every entry seen so far, since the same track might be reached by several paths
+    visited: Mutex<HashSet<PathBuf>>,
+}
+
+#[derive(Debug, Default)]
+struct PendingDir {
+    /// Names of files, that could be tracks, and weren't scanned yet
+    remaining: HashSet<OsString>,
+    packs: Vec<Pack>,
+}
+
+impl Pending {
+    /// Whether `path` is seen for the first time
+    pub fn visit(&self, path: &Path) -> bool {
+        self.visited
+            .lock()
+            .expect(MUTEX_LOCK_EXPECT_MSG)
+            .insert(path.to_owned())
+    }
+
+    /// Holds `packs` of the file at `path`, until its directory is fully scanned. Files, that
+    /// are filtered out by the walker, never show up here, so their directories wait until
+    /// [`Pending::finish`]
+    pub fn scanned(
+        &self,
+        tx: &PacksTx,
+        path: &Path,
+        packs: Vec<Pack>,
+        cli: &Cli,
+        listings: &Listings,
+    ) {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            send_unique(tx, packs);
+            return;
+        };
+        if packs.is_empty() && !could_be_track(name, cli.strictness, &cli.exts) {
+            return;
+        }
+
+        let mut dirs = self.dirs.lock().expect(MUTEX_LOCK_EXPECT_MSG);
+        let pending = dirs.entry(dir.to_owned()).or_insert_with(|| PendingDir {
+            remaining: listings
+                .get(dir)
+                .entries()
+                .filter(|name| could_be_track(name, cli.strictness, &cli.exts))
+                .map(OsStr::to_owned)
+                .collect(),
+            packs: Vec::new(),
+        });
+        pending.remaining.remove(name);
+        pending.packs.extend(packs);
+
+        if pending.remaining.is_empty() {
+            let done = dirs.remove(dir).unwrap_or_default();
+            drop(dirs);
+            send_unique(tx, done.packs);
+        }
+    }
+
+    /// Sends packs of all the directories, that weren't sent yet
+    pub fn finish(self, tx: &PacksTx) {
+        let dirs = self.dirs.into_inner().expect(MUTEX_LOCK_EXPECT_MSG);
+        for pending in dirs.into_values() {
+            send_unique(tx, pending.packs);
+        }
+    }
+}
+
+/// Whether a file named `name` might turn out to be a track, according to `strictness`
+fn could_be_track(name: &OsStr, strictness: FileMatchStrictness, exts: &AudioExts) -> bool {
+    match strictness {
+        FileMatchStrictness::TrustyGuesser => true,
+        FileMatchStrictness::FilterByExt | FileMatchStrictness::Paranoid => Path::new(name)
+            .extension()
+            .is_some_and(|ext| exts.contains(ext)),
+    }
+}
+
+/// Whether `path` is named like a `.lrc` or a `.nolrc` file
+pub fn is_lyrics(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("lrc") || ext.eq_ignore_ascii_case("nolrc"))
+}
+
+/// Sends packs for the paths read from `input`, separated by newlines, or by NUL with `-0`.
+/// Unlike [`prepare_entries`], directories aren't traversed, and packs are sent as soon as their
+/// paths are read. Since later paths aren't known yet, the first track to claim a lyrics file
+/// wins, and the rest are reported as [`PackError::Collision`]
+pub fn prepare_from_reader<R>(tx: &PacksTx, cli: &Cli, mut input: R, caches: Caches)
+where
+    R: BufRead,
+{
+    let separator = if cli.null { b'\0' } else { b'\n' };
+    let mut targets = HashMap::new();
+
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match input.read_until(separator, &mut buf) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                send(tx, Err(e.into()));
+                break;
+            }
+        }
+
+        if buf.last() == Some(&separator) {
+            buf.pop();
+        }
+        if separator == b'\n' && buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        if buf.is_empty() {
+            continue;
+        }
+
+        for path in playlist::expand(&[path_from_bytes(&buf)]) {
+            match from_entry(&path, cli, &caches) {
+                Ok(packs) => packs
+                    .into_iter()
+                    .for_each(|pack| send_first(tx, pack, &mut targets)),
+                Err(e) => send(tx, Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt as _;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Sends `pack` over `tx`, unless its lyrics file is already claimed by another track in
+/// `targets`. In that case, it's reported as [`PackError::Collision`] instead. A track, that's
+/// seen again, e.g. when it's listed twice, is only sent once
+pub fn send_first(tx: &PacksTx, pack: Pack, targets: &mut HashMap<PathBuf, PathBuf>) {
+    let target = pack.sidecar.lrc();
+    match targets.get(&target) {
+        Some(first) if *first == pack.path => {
+            tracing::debug!(path = %pack.path.display(), "track was already seen, skipping");
+        }
+        Some(first) => {
+            let others = vec![first.clone()];
+            send(
+                tx,
+                Err(PackError::Collision {
+                    path: pack.path,
+                    target,
+                    others,
+                }),
+            );
+        }
+        None => {
+            targets.insert(target, pack.path.clone());
+            send(tx, Ok(pack));
+        }
+    }
+}
+
+const MUTEX_LOCK_EXPECT_MSG: &str = "walker thread panicked while holding the lock";
+
+pub fn send(tx: &PacksTx, res: PackResult) {
+    tracing::trace!(?res, "sending result over");
+    tx.send(res)
+        .expect("this channel is unbounded, and, therefore, should always be available to send to");
+}
+
+/// Sends all the `packs` over `tx`, except for the ones that share lyrics files with each
+/// other. These are reported as [`PackError::Collision`] instead, since writing to the same file
+/// from multiple tracks would silently overwrite lyrics
+fn send_unique(tx: &PacksTx, packs: Vec<Pack>) {
+    let mut targets: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for pack in &packs {
+        targets
+            .entry(pack.sidecar.lrc())
+            .or_default()
+            .push(pack.path.clone());
+    }
+
+    for pack in packs {
+        let target = pack.sidecar.lrc();
+        let paths = &targets[&target];
+        if paths.len() == 1 {
+            send(tx, Ok(pack));
+        } else {
+            let others = paths
+                .iter()
+                .filter(|path| **path != pack.path)
+                .cloned()
+                .collect();
+            send(
+                tx,
+                Err(PackError::Collision {
+                    path: pack.path,
+                    target,
+                    others,
+                }),
+            );
+        }
+    }
+}
+
+/// Per-directory state, shared by all the walker threads
+#[derive(Debug, Default)]
+pub struct Caches {
+    listings: Listings,
+    overrides: Overrides,
+    sheets: CueSheets,
+    index: Option<Arc<Index>>,
+    progress: Option<Arc<Progress>>,
+    output: Option<Arc<Output>>,
+}
+
+impl Caches {
+    pub fn new(
+        index: Option<Arc<Index>>,
+        progress: Option<Arc<Progress>>,
+        output: Option<Arc<Output>>,
+    ) -> Self {
+        Self {
+            index,
+            progress,
+            output,
+            ..Self::default()
+        }
+    }
+
+    pub fn listings(&self) -> &Listings {
+        &self.listings
+    }
+
+    /// CUE sheet describing the file at `path`, unless `cli` turns them off
+    pub fn sheet(&self, path: &Path, cli: &Cli) -> Option<Arc<CueSheet>> {
+        if cli.no_cue || !path.extension().is_some_and(|ext| cli.exts.contains(ext)) {
+            return None;
+        }
+        self.sheets.find(path, &self.listings)
+    }
+
+    /// Reports a track, that's skipped because of its existing lyrics files
+    fn skipped(&self, path: &Path, request: Option<&LyricsRequest>, lyrics: Option<PathBuf>) {
+        if let Some(output) = &self.output {
+            output.track(&output::Track {
+                path: path.to_owned(),
+                request: request.map(Into::into),
+                outcome: Outcome::SkippedExisting,
+                lyrics,
+                lrclib_id: None,
+                error: None,
+            });
+        }
+    }
+}
+
+/// Packs for the file at `path`. A file described by a CUE sheet results in a pack for every
+/// CUE track, any other track results in a single pack
+#[tracing::instrument(level = "trace", skip(caches))]
+pub fn from_entry(path: &Path, cli: &Cli, caches: &Caches) -> Result<Vec<Pack>, PackError> {
+    packs_of(path, cli, caches).map_err(|e| match e {
+        PackError::Lofty(_) | PackError::Io(_) | PackError::RequestPrepare { .. } => {
+            PackError::Track {
+                path: path.to_owned(),
+                source: Box::new(e),
+            }
+        }
+        e => e,
+    })
+}
+
+fn packs_of(path: &Path, cli: &Cli, caches: &Caches) -> Result<Vec<Pack>, PackError> {
+    let listings = &caches.listings;
     if !path.is_file() {
         tracing::debug!(path = %path.display(), "entry is not a file");
-        return Ok(None);
+        return Ok(Vec::new());
+    }
+    if let Some(progress) = &caches.progress {
+        progress.scanned();
     }
 
-    let mut path_owned = path.to_owned();
-    let filter_pass = match cli.lrc_acquire_behavior {
-        LrcAcquireBehavior::All => true,
-        LrcAcquireBehavior::OverwriteExceptNolrc => !has_nolrc(&mut path_owned),
-        LrcAcquireBehavior::LrcMissingAll => !has_lrc(&mut path_owned),
-        LrcAcquireBehavior::LrcMissing => !has_lrc(&mut path_owned) && !has_nolrc(&mut path_owned),
+    if let Some(sheet) = caches.sheet(path, cli) {
+        return cue_packs(path, &sheet, cli, caches);
+    }
+
+    // Override files may change without touching the track, so tracks with overrides aren't
+    // indexed
+    let stamp = Stamp::of(path).filter(|_| !caches.overrides.exist(path, listings));
+    if let (Some(index), Some(stamp)) = (&caches.index, stamp) {
+        if let Some(skip) = index.skip(path, stamp, cli, listings) {
+            caches.skipped(path, Some(&skip.request), skip.lrc);
+            return Ok(Vec::new());
+        }
+    }
+
+    // Checking for existing lyrics is cheap, so do it before reading tags, if possible
+    let needs_tags = names_need_tags(cli);
+    if !needs_tags && !acquire_filter_pass(path, &Fields::default(), cli, listings) {
+        // Tracks aren't told apart from other files yet, so only those with known extensions are
+        // reported
+        if path.extension().is_some_and(|ext| cli.exts.contains(ext)) {
+            let lyrics = find_lrc(path, &Fields::default(), cli, listings);
+            caches.skipped(path, None, lyrics);
+        }
+        return Ok(Vec::new());
+    }
+
+    let Some(over) = overrides_for(path, caches) else {
+        return Ok(Vec::new());
     };
-    if !filter_pass {
-        return Ok(None);
-    }
-
-    // Restore the original path after modifying its value
-    let os_string = path_owned.as_mut_os_string();
-    os_string.clear();
-    os_string.push(path.as_os_str());
-    let path = path_owned;
-
-    let ext_matches = path
-        .extension()
-        .map(|ext| {
-            ext.eq_ignore_ascii_case("aac")
-                || ext.eq_ignore_ascii_case("alac")
-                || ext.eq_ignore_ascii_case("flac")
-                || ext.eq_ignore_ascii_case("mp3")
-                || ext.eq_ignore_ascii_case("m4a")
-                || ext.eq_ignore_ascii_case("ogg")
-                || ext.eq_ignore_ascii_case("opus")
-                || ext.eq_ignore_ascii_case("wav")
-        })
-        .unwrap_or(false);
+    let Some(tagged_file) = inspect(path, cli)? else {
+        return Ok(Vec::new());
+    };
+    let over = over?;
+
+    let mut tags = read_tags(&tagged_file, path, cli);
+    tags.apply_override(&over);
+    let mut values = tags.filter_values(&tagged_file);
+    values.duration = over.duration().or(values.duration);
+    if !tag_filter_pass(path, None, &values, cli) {
+        return Ok(Vec::new());
+    }
+    let instrumental = match over.instrumental {
+        Some(true) => Some(Instrumental::Override),
+        Some(false) => None,
+        None => guess::guess(path, &values, &cli.instrumental),
+    };
+    let track = tags.track;
+    let mut alt_artists = std::mem::take(&mut tags.alt_artists);
+    let mut request = prepare_lyrics_request(tags)?;
+    if let Some(duration) = over.duration() {
+        request.duration = Some(duration);
+    }
+    let fields = Fields {
+        artist: Some(&request.artist),
+        title: Some(&request.title),
+        track,
+    };
+    if needs_tags && !acquire_filter_pass(path, &fields, cli, listings) {
+        let lrc = find_lrc(path, &fields, cli, listings);
+        caches.skipped(path, Some(&request), lrc.clone());
+        if let (Some(index), Some(stamp)) = (&caches.index, stamp) {
+            let entry = index::Entry {
+                stamp,
+                lrc,
+                nolrc: find_nolrc(path, &fields, cli, listings),
+                request,
+                outcome: Outcome::SkippedExisting,
+            };
+            index.record(path, entry);
+        }
+        return Ok(Vec::new());
+    }
+    let sidecar = Sidecar::for_track(path, &fields, cli);
+
+    // Names of lyrics files are based on the original tags, only the request is rewritten
+    if let Some(rewrites) = &cli.rewrite_rules {
+        rewrite(path, &mut request, &mut alt_artists, rewrites, &over);
+    }
+
+    Ok(vec![Pack {
+        request,
+        alt_artists,
+        lrclib_id: over.lrclib_id,
+        instrumental,
+        path: path.to_owned(),
+        sidecar,
+        stamp,
+    }])
+}
+
+/// Whether the track at `path` matches the tag filters of `cli`. `track` is the number of a CUE
+/// track
+fn tag_filter_pass(path: &Path, track: Option<u32>, values: &Values, cli: &Cli) -> bool {
+    let pass = cli.tags.matches(values);
+    if !pass {
+        tracing::debug!(path = %path.display(), track, "track doesn't match tag filters, skipping");
+    }
+    pass
+}
+
+/// Overrides for the track at `path`. Returns [`None`], if the track has to be skipped. This is
+/// checked before reading the track, while errors are only worth reporting for actual tracks, so
+/// they're left to the caller
+pub fn overrides_for(path: &Path, caches: &Caches) -> Option<Result<Override, PackError>> {
+    let over = caches
+        .overrides
+        .get(path, &caches.listings)
+        .map_err(|(path, source)| PackError::Override { path, source });
+
+    if over.as_ref().is_ok_and(Override::skip) {
+        tracing::info!(path = %path.display(), "skipping a track, as specified by overrides");
+        None
+    } else {
+        Some(over)
+    }
+}
+
+/// Overrides for the CUE track `number` of the file at `path`
+pub fn cue_overrides_for(path: &Path, number: u32, caches: &Caches) -> Result<Override, PackError> {
+    caches
+        .overrides
+        .get_cue_track(path, &cue_track_stem(path, number), &caches.listings)
+        .map_err(|(path, source)| PackError::Override { path, source })
+}
+
+/// Metadata of the CUE `track` of a file with `tags`, described by `sheet`. Overrides take
+/// precedence over the sheet, which takes precedence over the tags
+pub fn cue_tags(sheet: &CueSheet, track: &CueTrack, over: &Override, tags: &Tags) -> Tags {
+    Tags {
+        artist: over
+            .artist
+            .clone()
+            .or_else(|| track.performer.clone())
+            .or_else(|| sheet.performer.clone())
+            .or_else(|| tags.artist.clone()),
+        alt_artists: Vec::new(),
+        title: over.title.clone().or_else(|| track.title.clone()),
+        album: over
+            .album
+            .clone()
+            .or_else(|| sheet.title.clone())
+            .or_else(|| tags.album.clone()),
+        track: Some(track.number),
+    }
+}
+
+/// Packs for every track of a single-file rip at `path`, described by `sheet`. Values from the
+/// sheet take precedence over the tags of the file, and overrides take precedence over both.
+/// Lyrics files are named after the track numbers, e.g. `album.01.lrc`
+fn cue_packs(
+    path: &Path,
+    sheet: &CueSheet,
+    cli: &Cli,
+    caches: &Caches,
+) -> Result<Vec<Pack>, PackError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tracks = sheet.tracks_of(&name).unwrap_or_default();
+
+    let Some(over) = overrides_for(path, caches) else {
+        return Ok(Vec::new());
+    };
+    let Some(tagged_file) = inspect(path, cli)? else {
+        return Ok(Vec::new());
+    };
+    over?;
+    let tags = read_tags(&tagged_file, path, cli);
+    let listings = &caches.listings;
+    let total = Some(tagged_file.properties().duration()).filter(|d| !d.is_zero());
+
+    let mut packs = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let over = cue_overrides_for(path, track.number, caches)?;
+        if over.skip() {
+            tracing::info!(
+                path = %path.display(),
+                track = track.number,
+                "skipping a CUE track, as specified by overrides",
+            );
+            continue;
+        }
+
+        let sidecar = Sidecar::for_cue_track(path, track.number, cli);
+        let pass = acquire_pass(
+            cli,
+            || listings.find(&sidecar.lrc()).is_some(),
+            || {
+                listings
+                    .find(&sidecar.nolrc())
+                    .is_some_and(|nolrc| nolrc_holds(&nolrc, cli))
+            },
+        );
+        if !pass {
+            tracing::debug!(
+                path = %path.display(),
+                track = track.number,
+                "not fetching lyrics for a CUE track with existing lyrics files",
+            );
+            caches.skipped(path, None, listings.find(&sidecar.lrc()));
+            continue;
+        }
+
+        let track_tags = cue_tags(sheet, track, &over, &tags);
+        let (Some(artist), Some(title)) = (track_tags.artist, track_tags.title) else {
+            tracing::warn!(
+                path = %path.display(),
+                track = track.number,
+                "CUE track has no performer or title, skipping",
+            );
+            continue;
+        };
+
+        let mut request = LyricsRequest {
+            artist,
+            title,
+            album: track_tags.album,
+            duration: over
+                .duration()
+                .or_else(|| track.duration(tracks.get(i + 1), total)),
+        };
+        let values = Values {
+            artist: Some(request.artist.clone()),
+            title: Some(request.title.clone()),
+            album: request.album.clone(),
+            track: Some(track.number),
+            duration: request.duration,
+            ..tags.filter_values(&tagged_file)
+        };
+        if !tag_filter_pass(path, Some(track.number), &values, cli) {
+            continue;
+        }
+        let instrumental = match over.instrumental {
+            Some(true) => Some(Instrumental::Override),
+            Some(false) => None,
+            None => guess::guess(path, &values, &cli.instrumental),
+        };
+        if let Some(rewrites) = &cli.rewrite_rules {
+            rewrite(path, &mut request, &mut [], rewrites, &over);
+        }
+
+        packs.push(Pack {
+            request,
+            alt_artists: Vec::new(),
+            lrclib_id: over.lrclib_id,
+            instrumental,
+            path: path.to_owned(),
+            sidecar,
+            stamp: None,
+        });
+    }
+
+    Ok(packs)
+}
+
+/// Reads the file at `path`, if it's a track, according to [`FileMatchStrictness`]
+pub fn inspect(path: &Path, cli: &Cli) -> Result<Option<TaggedFile>, PackError> {
+    let ext_matches = path.extension().is_some_and(|ext| cli.exts.contains(ext));
 
     let tagged_file = match cli.strictness {
         FileMatchStrictness::Paranoid | FileMatchStrictness::FilterByExt if !ext_matches => {
@@ -125,47 +811,123 @@ fn from_entry(path: &Path, cli: &Cli) -> Result<Option<(LyricsRequest, PathBuf)>
         }
 
         FileMatchStrictness::FilterByExt | FileMatchStrictness::TrustyGuesser => {
-            tracing::debug!(path = %path.display(), %ext_matches, "probing by extension");
-            shallow_inspect(&path)?
+            if FileType::from_path(path).is_some() {
+                tracing::debug!(path = %path.display(), %ext_matches, "probing by extension");
+                shallow_inspect(path)?
+            } else {
+                // lofty doesn't know the extension, added with --include-ext
+                tracing::debug!(path = %path.display(), %ext_matches, "probing by contents");
+                deep_inspect(path)?
+            }
         }
 
         FileMatchStrictness::Paranoid => {
             tracing::debug!(path = %path.display(), %ext_matches, "deep probing");
-            deep_inspect(&path)?
+            deep_inspect(path)?
         }
     };
 
     tracing::trace!(path = %path.display(), "probing ok");
-    Ok(Some((prepare_lyrics_request(tagged_file)?, path)))
+    Ok(Some(tagged_file))
+}
+
+/// Whether lyrics have to be acquired for a track, according to
+/// [`LrcAcquireBehavior`] and existing lyrics files
+fn acquire_filter_pass(path: &Path, fields: &Fields, cli: &Cli, listings: &Listings) -> bool {
+    acquire_pass(
+        cli,
+        || has_lrc(path, fields, cli, listings),
+        || has_nolrc(path, fields, cli, listings),
+    )
+}
+
+pub fn acquire_pass<L, N>(cli: &Cli, has_lrc: L, has_nolrc: N) -> bool
+where
+    L: FnOnce() -> bool,
+    N: FnOnce() -> bool,
+{
+    match cli.lrc_acquire_behavior {
+        LrcAcquireBehavior::All => true,
+        LrcAcquireBehavior::OverwriteExceptNolrc => !has_nolrc(),
+        LrcAcquireBehavior::LrcMissingAll => !has_lrc(),
+        LrcAcquireBehavior::LrcMissing => !has_lrc() && !has_nolrc(),
+    }
+}
+
+fn alt_lrc_names(cli: &Cli) -> impl Iterator<Item = &LrcTemplate> {
+    cli.alt_lrc_name.iter().filter(|_| !cli.no_alt_lrc_names)
+}
+
+/// Whether names of lyrics files depend on the tags of tracks
+pub fn names_need_tags(cli: &Cli) -> bool {
+    cli.lrc_name.needs_tags() || alt_lrc_names(cli).any(|t| t.needs_tags())
+}
+
+/// Paths, where lyrics of the track at `path` are looked for, in the order of preference
+fn lrc_paths<'a>(
+    path: &'a Path,
+    fields: &'a Fields,
+    cli: &'a Cli,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    iter::once(Sidecar::for_track(path, fields, cli).lrc()).chain(alt_lrc_names(cli).map(
+        move |template| path.with_file_name(template.expand(path, fields, cli.lang.as_deref())),
+    ))
+}
+
+/// Paths of all the lyrics files, that belong to the track at `path`. These are the ones looked
+/// for by [`find_lrc`] and [`find_nolrc`]
+pub fn lyrics_paths<'a>(
+    path: &'a Path,
+    fields: &'a Fields,
+    cli: &'a Cli,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    lrc_paths(path, fields, cli).chain(iter::once(Sidecar::for_track(path, fields, cli).nolrc()))
+}
+
+/// Looks for an existing lyrics file of a track at `path`, taking alternative names into account
+pub fn find_lrc(path: &Path, fields: &Fields, cli: &Cli, listings: &Listings) -> Option<PathBuf> {
+    lrc_paths(path, fields, cli).find_map(|lrc| listings.find(&lrc))
+}
+
+/// Whether the `.nolrc` file at `nolrc` still counts. With `--retry-not-found`, the ones for
+/// tracks, that weren't found, don't
+pub fn nolrc_holds(nolrc: &Path, cli: &Cli) -> bool {
+    !cli.retry_not_found || !NoLyrics::read(nolrc).is_retryable()
+}
+
+/// Looks for an existing `.nolrc` file of a track at `path`
+pub fn find_nolrc(path: &Path, fields: &Fields, cli: &Cli, listings: &Listings) -> Option<PathBuf> {
+    listings.find(&Sidecar::for_track(path, fields, cli).nolrc())
 }
 
-#[tracing::instrument(level = "trace", skip(path))]
-fn has_lrc(path: &mut PathBuf) -> bool {
-    let res = path.set_extension("lrc") && path.exists();
-    if res {
+#[tracing::instrument(level = "trace", skip_all)]
+fn has_lrc(path: &Path, fields: &Fields, cli: &Cli, listings: &Listings) -> bool {
+    let found = find_lrc(path, fields, cli, listings);
+    if let Some(lrc) = &found {
         tracing::info!(
-            path = %path.display(),
+            path = %lrc.display(),
             "not fetching lyrics for a file with corresponding .lrc file",
         );
     }
 
-    res
+    found.is_some()
 }
 
-#[tracing::instrument(level = "trace", skip(path))]
-fn has_nolrc(path: &mut PathBuf) -> bool {
-    let res = path.set_extension("nolrc") && path.exists();
-    if res {
+#[tracing::instrument(level = "trace", skip_all)]
+fn has_nolrc(path: &Path, fields: &Fields, cli: &Cli, listings: &Listings) -> bool {
+    let found = find_nolrc(path, fields, cli, listings).filter(|nolrc| nolrc_holds(nolrc, cli));
+    if let Some(nolrc) = &found {
         tracing::info!(
-            path = %path.display(),
+            path = %nolrc.display(),
             "not fetching lyrics for a file with corresponding .nolrc file",
         );
     }
 
-    res
+    found.is_some()
 }
 
-fn deep_inspect(path: &Path) -> Result<TaggedFile, PackError> {
+/// Reads the file at `path`, guessing its type by its contents
+pub fn deep_inspect(path: &Path) -> Result<TaggedFile, PackError> {
     Ok(Probe::open(path)?.guess_file_type()?.read()?)
 }
 
@@ -173,22 +935,220 @@ fn shallow_inspect(path: &Path) -> Result<TaggedFile, PackError> {
     Ok(read_from_path(path)?)
 }
 
-#[tracing::instrument(level = "trace", skip(file))]
-fn prepare_lyrics_request(file: TaggedFile) -> Result<LyricsRequest, PackError> {
-    let tags_slice = file.tags();
-
-    let artist = tags_slice
-        .iter()
-        .find_map(|tags| tags.artist())
-        .map(|cow| cow.into_owned());
-    let title = tags_slice
-        .iter()
-        .find_map(|tags| tags.title())
-        .map(|cow| cow.into_owned());
-    let album = tags_slice
-        .iter()
-        .find_map(|tags| tags.album())
-        .map(|cow| cow.into_owned());
+/// Metadata of a track, read from its tags and, if they're missing, derived from its path
+#[derive(Debug, Default)]
+pub struct Tags {
+    pub artist: Option<String>,
+    /// Individual artists, if there are several of them, excluding [`Tags::artist`]
+    pub alt_artists: Vec<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+}
+
+impl Tags {
+    fn read(file: &TaggedFile, artist_separators: &[String]) -> Self {
+        let tags_slice = file.tags();
+
+        let mut artist = tags_slice
+            .iter()
+            .find_map(|tags| tags.artist())
+            .map(|cow| cow.into_owned());
+        let mut artists = tags_slice
+            .iter()
+            .flat_map(|tags| {
+                tags.get_strings(ItemKey::TrackArtist)
+                    .chain(tags.get_strings(ItemKey::TrackArtists))
+            })
+            .collect::<Vec<_>>();
+        if artist.is_none() {
+            artist = tags_slice
+                .iter()
+                .find_map(|tags| tags.get_string(ItemKey::AlbumArtist))
+                .map(str::to_owned);
+            artists.extend(
+                tags_slice
+                    .iter()
+                    .flat_map(|tags| tags.get_strings(ItemKey::AlbumArtist)),
+            );
+            if let Some(artist) = &artist {
+                tracing::debug!(artist, "artist tag is missing, using album artist instead");
+            }
+        }
+
+        let mut alt_artists = Vec::new();
+        for name in artists
+            .into_iter()
+            .flat_map(|value| split_artists(value, artist_separators))
+        {
+            let is_dup = artist
+                .iter()
+                .chain(&alt_artists)
+                .any(|other| other.eq_ignore_ascii_case(&name));
+            if !is_dup {
+                alt_artists.push(name);
+            }
+        }
+
+        Self {
+            artist,
+            alt_artists,
+            title: tags_slice
+                .iter()
+                .find_map(|tags| tags.title())
+                .map(|cow| cow.into_owned()),
+            album: tags_slice
+                .iter()
+                .find_map(|tags| tags.album())
+                .map(|cow| cow.into_owned()),
+            track: tags_slice.iter().find_map(|tags| tags.track()),
+        }
+    }
+
+    /// Replaces values with the ones set by `over`
+    pub fn apply_override(&mut self, over: &Override) {
+        if let Some(artist) = &over.artist {
+            self.artist = Some(artist.clone());
+            self.alt_artists.clear();
+        }
+        if let Some(title) = &over.title {
+            self.title = Some(title.clone());
+        }
+        if let Some(album) = &over.album {
+            self.album = Some(album.clone());
+        }
+    }
+
+    /// Values for tag filters. Tags, that aren't needed for requests, are read from `file`
+    fn filter_values(&self, file: &TaggedFile) -> Values {
+        let tags_slice = file.tags();
+        Values {
+            artist: self.artist.clone(),
+            album_artist: tags_slice
+                .iter()
+                .find_map(|tags| tags.get_string(ItemKey::AlbumArtist))
+                .map(str::to_owned),
+            album: self.album.clone(),
+            title: self.title.clone(),
+            genre: tags_slice
+                .iter()
+                .find_map(|tags| tags.genre())
+                .map(|cow| cow.into_owned()),
+            year: tags_slice
+                .iter()
+                .find_map(|tags| tags.date())
+                .map(|date| date.year.into()),
+            track: self.track,
+            duration: Some(file.properties().duration()).filter(|d| !d.is_zero()),
+        }
+    }
+
+    /// Fills in missing values from the first of `patterns` that matches `path`. Values, that
+    /// are present in tags, are kept as is
+    fn fill_from_path(&mut self, path: &Path, patterns: &[PathPattern]) {
+        if self.artist.is_some()
+            && self.title.is_some()
+            && self.album.is_some()
+            && self.track.is_some()
+        {
+            return;
+        }
+        let Some((pattern, fields)) = patterns
+            .iter()
+            .find_map(|pattern| Some((pattern, pattern.captures(path)?)))
+        else {
+            return;
+        };
+
+        fn fill<T>(
+            field: &'static str,
+            tag: &mut Option<T>,
+            value: Option<T>,
+            path: &Path,
+            pattern: &PathPattern,
+        ) where
+            T: Debug,
+        {
+            if tag.is_none() && value.is_some() {
+                tracing::info!(
+                    path = %path.display(),
+                    pattern = pattern.as_str(),
+                    field,
+                    value = ?value,
+                    "tag is missing, derived it from the path",
+                );
+                *tag = value;
+            }
+        }
+        fill("artist", &mut self.artist, fields.artist, path, pattern);
+        fill("title", &mut self.title, fields.title, path, pattern);
+        fill("album", &mut self.album, fields.album, path, pattern);
+        fill("track", &mut self.track, fields.track, path, pattern);
+    }
+}
+
+/// Splits a value of an artist tag into individual artists by any of `separators`. `\0`, which
+/// separates multiple values in ID3v2.4 frames, is always a separator
+fn split_artists(value: &str, separators: &[String]) -> Vec<String> {
+    let mut parts = value.split('\0').map(str::to_owned).collect::<Vec<_>>();
+    for separator in separators {
+        parts = parts
+            .iter()
+            .flat_map(|part| part.split(separator.as_str()))
+            .map(str::to_owned)
+            .collect();
+    }
+
+    parts
+        .into_iter()
+        .map(|part| part.trim().to_owned())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Applies `rewrites` to the artist, title and album of `request`, and to `alt_artists`.
+/// Values set by `over` are left as is
+fn rewrite(
+    path: &Path,
+    request: &mut LyricsRequest,
+    alt_artists: &mut [String],
+    rewrites: &Rewrites,
+    over: &Override,
+) {
+    let values = iter::once((Field::Artist, &mut request.artist))
+        .chain(iter::once((Field::Title, &mut request.title)))
+        .chain(request.album.as_mut().map(|album| (Field::Album, album)))
+        .chain(alt_artists.iter_mut().map(|artist| (Field::Artist, artist)))
+        .filter(|(field, _)| match field {
+            Field::Artist => over.artist.is_none(),
+            Field::Title => over.title.is_none(),
+            Field::Album => over.album.is_none(),
+        });
+
+    for (field, value) in values {
+        if let Some(new) = rewrites.apply(field, value) {
+            tracing::info!(path = %path.display(), %field, from = value, to = new, "rewrote tag");
+            *value = new;
+        }
+    }
+}
+
+/// Reads metadata of the track at `path`, falling back to `--path-pattern` for missing tags
+pub fn read_tags(file: &TaggedFile, path: &Path, cli: &Cli) -> Tags {
+    let mut tags = Tags::read(file, &cli.artist_separator);
+    tags.fill_from_path(path, &cli.path_pattern);
+    tags
+}
+
+#[tracing::instrument(level = "trace")]
+pub fn prepare_lyrics_request(tags: Tags) -> Result<LyricsRequest, PackError> {
+    let Tags {
+        artist,
+        title,
+        album,
+        alt_artists: _,
+        track: _,
+    } = tags;
     let duration = None; // TODO
 
     if title.is_none() || artist.is_none() || album.is_none() {
@@ -0,0 +1,81 @@
+use crate::cli::InstrumentalGuess;
+use crate::filter::Values;
+use std::path::Path;
+use std::time::Duration;
+
+/// Why a track is treated as instrumental without asking LRCLIB
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instrumental {
+    /// Marked by overrides
+    Override,
+    /// Guessed from tags by [`guess`]
+    Guessed,
+}
+
+impl Instrumental {
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::Override => "marked as instrumental by overrides",
+            Self::Guessed => "guessed to be instrumental from tags",
+        }
+    }
+}
+
+/// Whether the track at `path` looks instrumental, according to `opts`. Titles and genres have to
+/// contain one of the keywords as whole words, ignoring case, e.g. `Intro` matches
+/// `Intro (Live)`, but not `Introduction`
+pub fn guess(path: &Path, values: &Values, opts: &InstrumentalGuess) -> Option<Instrumental> {
+    if !opts.guess_instrumental {
+        return None;
+    }
+
+    let title = values.title.as_deref().and_then(|title| {
+        opts.instrumental_keyword
+            .iter()
+            .find(|keyword| contains_words(title, keyword))
+    });
+    if let Some(keyword) = title {
+        tracing::info!(path = %path.display(), keyword, "title looks instrumental");
+        return Some(Instrumental::Guessed);
+    }
+
+    let genre = values.genre.as_deref().and_then(|genre| {
+        opts.instrumental_genre
+            .iter()
+            .find(|keyword| contains_words(genre, keyword))
+    });
+    if let Some(keyword) = genre {
+        tracing::info!(path = %path.display(), keyword, "genre looks instrumental");
+        return Some(Instrumental::Guessed);
+    }
+
+    let min = opts.min_vocal_duration.map(Duration::from_secs);
+    if let (Some(duration), Some(min)) = (values.duration, min) {
+        if duration < min {
+            tracing::info!(
+                path = %path.display(),
+                ?duration,
+                "track is too short to have lyrics",
+            );
+            return Some(Instrumental::Guessed);
+        }
+    }
+
+    None
+}
+
+/// Whether `haystack` contains `needle`, ignoring case, and not as a part of a longer word
+fn contains_words(haystack: &str, needle: &str) -> bool {
+    let haystack = haystack.to_lowercase();
+    let needle = needle.to_lowercase();
+    if needle.is_empty() {
+        return false;
+    }
+
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    haystack.match_indices(&needle).any(|(i, _)| {
+        let before = haystack[..i].chars().next_back();
+        let after = haystack[i + needle.len()..].chars().next();
+        !is_word(before) && !is_word(after)
+    })
+}
@@ -0,0 +1,240 @@
+use super::atomic;
+use super::listing::Listings;
+use super::outcome::Outcome;
+use crate::cli::Cli;
+use crate::remote::LyricsRequest;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Default location of the index
+pub fn default_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(clap::crate_name!()).join("index.json"))
+}
+
+/// Size and modification time of a file. If neither changed, the file is assumed to be the same
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    size: u64,
+    mtime: SystemTime,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some(Self {
+            size: meta.len(),
+            mtime: meta.modified().ok()?,
+        })
+    }
+}
+
+/// What's known about a track from previous runs
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub stamp: Stamp,
+    pub request: LyricsRequest,
+    pub outcome: Outcome,
+    /// Lyrics file the track had, or got, at the time
+    pub lrc: Option<PathBuf>,
+    /// `.nolrc` file the track had, or got, at the time
+    pub nolrc: Option<PathBuf>,
+}
+
+/// Track skipped by [`Index::skip`]
+#[derive(Debug)]
+pub struct Skip {
+    pub request: LyricsRequest,
+    /// Lyrics file of the track, if it has one
+    pub lrc: Option<PathBuf>,
+}
+
+/// Persisted results of previous runs, keyed by absolute paths of tracks. Lets unchanged tracks
+/// with a known outcome be skipped without reading their tags. Tracks of CUE sheets aren't
+/// indexed
+#[derive(Debug)]
+pub struct Index {
+    path: PathBuf,
+    /// Options of this run, see [`Indexed::settings`]
+    settings: String,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Inner {
+    files: HashMap<PathBuf, Indexed>,
+    /// Tracks looked up during this run
+    #[serde(skip)]
+    seen: HashSet<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Indexed {
+    /// Options that affect the names of lyrics files or which tracks are requested, when the
+    /// track was indexed. If they differ from the ones of the current run, the entry is stale
+    settings: String,
+    entry: Entry,
+}
+
+impl Index {
+    /// Reads the index at `path`. Starts from scratch, if there's none yet, it can't be read,
+    /// or `cli` asks for a full rescan. Entries made with different options are kept, but
+    /// aren't used
+    pub fn load(path: PathBuf, cli: &Cli) -> Self {
+        let inner = if cli.full_rescan { None } else { read(&path) };
+
+        Self {
+            path,
+            settings: settings(cli),
+            inner: Mutex::new(inner.unwrap_or_default()),
+        }
+    }
+
+    /// Whether the track at `path` can be skipped without reading it, according to `cli`. That's
+    /// the case, if it hasn't changed since it was indexed, and its lyrics files, or the lack of
+    /// lyrics, would make it skipped anyway. Returns what's known about the skipped track
+    pub fn skip(&self, path: &Path, stamp: Stamp, cli: &Cli, listings: &Listings) -> Option<Skip> {
+        let key = key_of(path)?;
+
+        let entry = {
+            let mut inner = self.lock();
+            inner.seen.insert(key.clone());
+            let indexed = inner.files.get(&key)?;
+            if indexed.entry.stamp != stamp {
+                return None;
+            }
+            if indexed.settings != self.settings {
+                tracing::debug!(path = %path.display(), "options changed since it was indexed");
+                return None;
+            }
+            // Lookups below may read directories, so they're done without holding the lock
+            indexed.entry.clone()
+        };
+
+        let exists = |file: &Option<PathBuf>| {
+            file.as_deref()
+                .is_some_and(|file| listings.find(file).is_some())
+        };
+        let nolrc = entry.nolrc.as_deref().and_then(|file| listings.find(file));
+        let skip = !super::file::acquire_pass(
+            cli,
+            || exists(&entry.lrc),
+            || {
+                // With --deny-nolrc, the index is the only place where it's recorded. Otherwise,
+                // a deleted .nolrc file means lyrics have to be requested again
+                let recorded = cli.deny_nolrc || nolrc.is_some();
+                match entry.outcome {
+                    Outcome::Instrumental => {
+                        recorded && nolrc.is_none_or(|nolrc| super::file::nolrc_holds(&nolrc, cli))
+                    }
+                    Outcome::NotFound => recorded && !cli.retry_not_found,
+                    _ => nolrc.is_some_and(|nolrc| super::file::nolrc_holds(&nolrc, cli)),
+                }
+            },
+        );
+        if !skip {
+            return None;
+        }
+
+        tracing::debug!(
+            path = %path.display(),
+            outcome = ?entry.outcome,
+            "track didn't change since the last run, skipping",
+        );
+        Some(Skip {
+            lrc: entry.lrc.as_deref().and_then(|lrc| listings.find(lrc)),
+            request: entry.request,
+        })
+    }
+
+    /// Records `entry` for the track at `path`. Outcomes, that may change on the next attempt,
+    /// are forgotten instead
+    pub fn record(&self, path: &Path, mut entry: Entry) {
+        let Some(key) = key_of(path) else {
+            return;
+        };
+
+        let mut inner = self.lock();
+        match entry.outcome {
+            Outcome::WrittenSynced
+            | Outcome::WrittenPlain
+            | Outcome::NotFound
+            | Outcome::Instrumental
+            | Outcome::SkippedExisting => {
+                entry.lrc = entry.lrc.as_deref().and_then(key_of);
+                entry.nolrc = entry.nolrc.as_deref().and_then(key_of);
+                let settings = self.settings.clone();
+                inner.files.insert(key, Indexed { settings, entry });
+            }
+            Outcome::TagError | Outcome::NetworkError | Outcome::WriteError | Outcome::Planned => {
+                inner.files.remove(&key);
+            }
+        }
+    }
+
+    /// Writes the index back. Tracks under `roots`, that weren't seen during this run, don't
+    /// exist anymore, so they're dropped
+    pub async fn save(&self, roots: &[PathBuf]) -> io::Result<()> {
+        let contents = {
+            let mut inner = self.lock();
+            let roots: Vec<_> = roots.iter().filter_map(|root| key_of(root)).collect();
+            let Inner { files, seen, .. } = &mut *inner;
+            files.retain(|path, _| {
+                seen.contains(path) || !roots.iter().any(|root| path.starts_with(root))
+            });
+            serde_json::to_vec(&*inner)?
+        };
+
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let staged = atomic::stage(&self.path, contents).await?;
+        atomic::commit(staged, &self.path).await
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .expect("thread panicked while holding the lock")
+    }
+}
+
+fn read(path: &Path) -> Option<Inner> {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            tracing::warn!(%e, path = %path.display(), "failed to read index, starting over");
+            return None;
+        }
+    };
+
+    serde_json::from_slice(&contents)
+        .inspect_err(
+            |e| tracing::warn!(%e, path = %path.display(), "failed to parse index, starting over"),
+        )
+        .ok()
+}
+
+/// Index key of `path`. Paths, that aren't valid UTF-8, can't be stored in JSON, so they aren't
+/// indexed
+fn key_of(path: &Path) -> Option<PathBuf> {
+    std::path::absolute(path)
+        .ok()
+        .filter(|path| path.to_str().is_some())
+}
+
+fn settings(cli: &Cli) -> String {
+    let alt_lrc_names = if cli.no_alt_lrc_names {
+        &[][..]
+    } else {
+        &cli.alt_lrc_name[..]
+    };
+    format!(
+        "{:?} {:?} {:?} {:?} {:?} {:?}",
+        cli.lrc_name, cli.lang, alt_lrc_names, cli.rewrite_rules, cli.tags, cli.instrumental,
+    )
+}
@@ -0,0 +1,261 @@
+use super::atomic;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt as _;
+
+const ENTRIES_FILE_NAME: &str = "journal.jsonl";
+const BACKUPS_DIR_NAME: &str = "backups";
+
+/// Default directory, where journals of all the runs are kept
+pub fn default_root() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join(clap::crate_name!()).join("journal"))
+}
+
+/// A single change to the filesystem, made during a run
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum Entry {
+    Create { path: PathBuf },
+    Overwrite { path: PathBuf, backup: PathBuf },
+    Delete { path: PathBuf, backup: PathBuf },
+}
+
+/// Record of every change made to the filesystem during a single run, along with backups of
+/// overwritten and deleted files. Nothing is created on the disk until there's a file to back up
+/// or a change to record, so runs that don't change anything don't leave empty journals behind
+#[derive(Debug)]
+pub struct Journal {
+    run_id: String,
+    root: PathBuf,
+    dir: PathBuf,
+    /// How many of the latest runs are kept by [`Journal::prune`]
+    keep: usize,
+    entries: tokio::sync::Mutex<Option<tokio::fs::File>>,
+    backup_count: AtomicUsize,
+}
+
+impl Journal {
+    /// Starts a journal for a new run inside of `root`. Only `keep` latest runs are kept there
+    pub fn new(root: &Path, keep: usize) -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let run_id = format!("{}-{}", secs, std::process::id());
+
+        Self {
+            root: root.to_owned(),
+            dir: root.join(&run_id),
+            keep,
+            run_id,
+            entries: tokio::sync::Mutex::new(None),
+            backup_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Whether anything was recorded to this journal
+    pub async fn is_empty(&self) -> bool {
+        self.entries.lock().await.is_none()
+    }
+
+    /// Saves a copy of the file at `path` before it's changed. Returns [`None`] if there's
+    /// nothing at `path`
+    pub async fn backup(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        // Checked first, so the run directory isn't created when there's nothing to back up
+        if !tokio::fs::try_exists(path).await? {
+            return Ok(None);
+        }
+        let dir = self.dir.join(BACKUPS_DIR_NAME);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let backup = dir.join(
+            self.backup_count
+                .fetch_add(1, Ordering::Relaxed)
+                .to_string(),
+        );
+        match tokio::fs::copy(path, &backup).await {
+            Ok(_) => Ok(Some(backup)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deletes journals of all the runs, except for the `keep` latest ones. The current run is
+    /// always kept. Directories, that aren't named like runs, are left alone
+    pub async fn prune(&self) {
+        let mut runs = match runs(&self.root).await {
+            Ok(runs) => runs,
+            Err(e) => {
+                tracing::warn!(%e, root = %self.root.display(), "failed to list journals");
+                return;
+            }
+        };
+        runs.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+
+        for (_, run_id) in runs.iter().skip(self.keep) {
+            if *run_id == self.run_id {
+                continue;
+            }
+            let dir = self.root.join(run_id);
+            match tokio::fs::remove_dir_all(&dir).await {
+                Ok(()) => tracing::debug!(run_id, "deleted old journal"),
+                Err(e) => tracing::warn!(%e, dir = %dir.display(), "failed to delete old journal"),
+            }
+        }
+    }
+
+    pub async fn record(&self, entry: &Entry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry).map_err(io::Error::other)?;
+        line.push(b'\n');
+
+        let mut lock = self.entries.lock().await;
+        let file = match &mut *lock {
+            Some(file) => file,
+            None => {
+                tokio::fs::create_dir_all(&self.dir).await?;
+                let file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(self.dir.join(ENTRIES_FILE_NAME))
+                    .await?;
+                lock.insert(file)
+            }
+        };
+
+        file.write_all(&line).await?;
+        file.sync_data().await
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UndoError {
+    #[error("couldn't find any runs to undo in \"{}\"", .0.display())]
+    NoRuns(PathBuf),
+    #[error("failed to read journal \"{}\": {}", .path.display(), .source)]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse journal \"{}\": {}", .path.display(), .source)]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("{0} changes couldn't be undone, the journal is kept")]
+    Incomplete(usize),
+}
+
+/// Reverts all the changes recorded in the journal of the run `run_id`, or the latest run,
+/// if it's [`None`]. The journal is deleted once everything is reverted
+#[tracing::instrument(level = "trace")]
+pub async fn undo(root: &Path, run_id: Option<&str>) -> Result<(), UndoError> {
+    let run_id = match run_id {
+        Some(run_id) => run_id.to_owned(),
+        None => latest_run(root)
+            .await
+            .ok_or_else(|| UndoError::NoRuns(root.to_owned()))?,
+    };
+    let dir = root.join(&run_id);
+    let path = dir.join(ENTRIES_FILE_NAME);
+
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|source| UndoError::Read {
+            path: path.clone(),
+            source,
+        })?;
+    let entries = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<Entry>, _>>()
+        .map_err(|source| UndoError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+
+    tracing::info!(run_id, count = entries.len(), "undoing changes");
+    let mut failed = 0;
+    for entry in entries.iter().rev() {
+        match revert(entry).await {
+            Ok(()) => tracing::info!(?entry, "reverted"),
+            Err(e) => {
+                tracing::warn!(%e, ?entry, "failed to revert");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(UndoError::Incomplete(failed));
+    }
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        tracing::warn!(%e, dir = %dir.display(), "failed to delete journal");
+    }
+
+    Ok(())
+}
+
+async fn revert(entry: &Entry) -> io::Result<()> {
+    match entry {
+        Entry::Create { path } => match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+        Entry::Overwrite { path, backup } | Entry::Delete { path, backup } => {
+            let content = tokio::fs::read(backup).await?;
+            let staged = atomic::stage(path, content).await?;
+            atomic::commit(staged, path).await
+        }
+    }
+}
+
+/// Ids of all the runs in `root`, along with their timestamps and process ids, that they're
+/// ordered by
+async fn runs(root: &Path) -> io::Result<Vec<((u64, u32), String)>> {
+    let mut read_dir = match tokio::fs::read_dir(root).await {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut res = Vec::new();
+    while let Some(entry) = read_dir.next_entry().await? {
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        let key = name
+            .split_once('-')
+            .and_then(|(secs, pid)| Some((secs.parse().ok()?, pid.parse().ok()?)));
+        if let Some(key) = key {
+            res.push((key, name));
+        }
+    }
+
+    Ok(res)
+}
+
+/// Id of the latest run in `root`, that recorded any changes. Run ids start with a timestamp
+/// and a process id, so that's the one with the greatest of them
+async fn latest_run(root: &Path) -> Option<String> {
+    let mut runs = runs(root).await.ok()?;
+    runs.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+
+    for (_, run_id) in runs {
+        let entries = root.join(&run_id).join(ENTRIES_FILE_NAME);
+        if tokio::fs::try_exists(&entries).await.unwrap_or(false) {
+            return Some(run_id);
+        }
+    }
+
+    None
+}
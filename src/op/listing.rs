@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Cache of directory contents. Allows checking whether some file exists without touching the
+/// filesystem for every single track, which is slow on network mounts. Directories are read
+/// only once, the first time they're requested
+#[derive(Debug, Default)]
+pub struct Listings {
+    dirs: Mutex<HashMap<PathBuf, Arc<Listing>>>,
+}
+
+/// Names of all the files in a single directory
+#[derive(Debug, Default)]
+pub struct Listing {
+    /// Names as they're on the disk
+    entries: Vec<OsString>,
+    /// Indices into `entries`, keyed by lowercased names
+    names: HashMap<String, usize>,
+}
+
+impl Listings {
+    pub fn get(&self, dir: &Path) -> Arc<Listing> {
+        if let Some(listing) = self.lock().get(dir) {
+            return listing.clone();
+        }
+
+        // Don't hold the lock while reading the directory, it might take a while. Worst case,
+        // the same directory is read twice by different threads
+        let listing = Arc::new(Listing::read(dir));
+        self.lock().entry(dir.to_owned()).or_insert(listing).clone()
+    }
+
+    /// Looks for a file at `path`, ignoring case of its name. Returns the path to the file, as
+    /// it's named on the disk
+    pub fn find(&self, path: &Path) -> Option<PathBuf> {
+        let name = self.get(path.parent()?).find(path.file_name()?)?.to_owned();
+        Some(path.with_file_name(name))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Arc<Listing>>> {
+        self.dirs
+            .lock()
+            .expect("walker thread panicked while holding the lock")
+    }
+}
+
+impl Listing {
+    #[tracing::instrument(level = "trace")]
+    fn read(dir: &Path) -> Self {
+        // An empty path means the current directory
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+
+        let entries: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name())
+                .collect(),
+            Err(e) => {
+                tracing::debug!(%e, dir = %dir.display(), "failed to list directory");
+                Vec::new()
+            }
+        };
+        let names = entries
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (key(name), i))
+            .collect();
+
+        Self { entries, names }
+    }
+
+    /// Looks for a file named `name`, ignoring case. Returns the name of the file, as it's named
+    /// on the disk
+    pub fn find(&self, name: &OsStr) -> Option<&OsStr> {
+        self.names
+            .get(&key(name))
+            .map(|&i| self.entries[i].as_os_str())
+    }
+
+    /// Names of all the files, as they're on the disk. Unlike [`Listing::names`], files, whose
+    /// names differ only in case, are all there
+    pub fn entries(&self) -> impl Iterator<Item = &OsStr> {
+        self.entries.iter().map(OsString::as_os_str)
+    }
+
+    /// Names of all the files, lowercased
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(String::as_str)
+    }
+}
+
+fn key(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
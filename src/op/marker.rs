@@ -0,0 +1,71 @@
+use std::io;
+use std::path::Path;
+
+/// Lyrics file for instrumental tracks, written with `--instrumental-lrc`
+pub const INSTRUMENTAL_LRC: &str = "[instrumental]\n";
+
+/// Whether `lyrics` are the ones written by `--instrumental-lrc`
+pub fn is_instrumental_lrc(lyrics: &str) -> bool {
+    lyrics.trim() == INSTRUMENTAL_LRC.trim()
+}
+
+/// Why a track has no lyrics. Written to its `.nolrc` file as a single word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoLyrics {
+    /// LRCLIB knows the track as instrumental
+    Instrumental,
+    /// LRCLIB doesn't have lyrics for the track yet
+    NotFound,
+    /// The track was found to be instrumental locally, by overrides or by guessing
+    Local,
+}
+
+impl NoLyrics {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Instrumental => "instrumental",
+            Self::NotFound => "not-found",
+            Self::Local => "local",
+        }
+    }
+
+    /// Whether lyrics might show up later, or the track was never asked about. Only these tracks
+    /// are retried with `--retry-not-found`
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::NotFound | Self::Local)
+    }
+
+    /// Contents of the `.nolrc` file
+    pub fn marker(self) -> String {
+        format!("{}\n", self.as_str())
+    }
+
+    /// Reason recorded in the `.nolrc` file at `path`. Files, that are empty, were written before
+    /// reasons were recorded, and are treated as [`NoLyrics::NotFound`], as well as unreadable ones
+    pub fn read(path: &Path) -> Self {
+        Self::from_read(path, std::fs::read_to_string(path))
+    }
+
+    /// Same as [`NoLyrics::read`], but without blocking the runtime
+    pub async fn read_async(path: &Path) -> Self {
+        Self::from_read(path, tokio::fs::read_to_string(path).await)
+    }
+
+    fn from_read(path: &Path, contents: io::Result<String>) -> Self {
+        match contents {
+            Ok(contents) => Self::parse(&contents),
+            Err(e) => {
+                tracing::warn!(%e, path = %path.display(), "failed to read nolrc file");
+                Self::NotFound
+            }
+        }
+    }
+
+    fn parse(contents: &str) -> Self {
+        match contents.trim() {
+            "instrumental" => Self::Instrumental,
+            "local" => Self::Local,
+            _ => Self::NotFound,
+        }
+    }
+}
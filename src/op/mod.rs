@@ -1,18 +1,41 @@
+mod atomic;
+pub mod clean;
 mod file;
+mod guess;
+mod index;
+pub mod journal;
+mod listing;
+mod marker;
+mod outcome;
+mod output;
+mod overrides;
+mod plan;
+pub mod progress;
+mod prune;
+mod sheets;
+pub mod stats;
+pub mod summary;
 #[cfg(test)]
 mod test;
+pub mod watch;
 
-use crate::cli::Cli;
+use crate::cli::{Cli, OutputFormat};
 use crate::remote::{LyricsError, LyricsRequest, LyricsResponse, Remote};
 use file::PackResult;
 use file::PacksRx;
+use file::{Caches, Pack, PackError, Sidecar};
+use guess::Instrumental;
+use index::Index;
+use journal::{Entry, Journal};
+use marker::{NoLyrics, INSTRUMENTAL_LRC};
+use outcome::{Handled, Outcome};
+use output::Output;
+use progress::Progress;
 use reqwest::StatusCode;
-use std::{
-    future::Future,
-    io,
-    path::{Path, PathBuf},
-    sync::Arc,
-};
+use std::path::{Path, PathBuf};
+use std::{future::Future, io, sync::Arc};
+use summary::Summary;
+use tokio::io::AsyncWriteExt as _;
 use tokio::task::JoinSet;
 
 const JOIN_HANDLE_EXPECT_MSG: &str =
@@ -20,134 +43,434 @@ const JOIN_HANDLE_EXPECT_MSG: &str =
 
 /// Starts up the whole process of going through tracks
 /// and creating corresponding `.lrc` and `.nolrc` files, taking `cli`
-/// configuration into account
+/// configuration into account. Returns the summary of the run, that was also printed
 ///
 /// To understand, why `remote` has to have all these type constraints,
 /// consult [`tokio::runtime::Runtime::spawn`]
 /// and [`tokio::task::JoinSet::spawn`] documentation
 #[tracing::instrument(level = "trace", skip(remote), parent = None)]
-pub async fn start_up<R>(remote: &'static R, cli: Cli)
+pub async fn start_up<R>(remote: &'static R, cli: Cli) -> Summary
 where
     R: Remote + Sync,
 {
-    let deny_nolrc = cli.deny_nolrc;
+    let output = Arc::new(Output::new(cli.output));
+    run(remote, cli, output).await
+}
+
+/// Same as [`start_up`], but reports tracks to `output`
+async fn run<R>(remote: &'static R, cli: Cli, output: Arc<Output>) -> Summary
+where
+    R: Remote + Sync,
+{
+    let opts = EntryOpts::from(&cli);
+    let progress = Arc::new(Progress::default());
+    let records = Records {
+        journal: open_journal(&cli),
+        index: open_index(&cli),
+        progress: Some(progress.clone()),
+        output: Some(output.clone()),
+    };
+    if let OutputFormat::Json = cli.output {
+        plan::to_stderr();
+    }
+    let shown = (!cli.no_progress).then(|| progress.show());
 
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<PackResult>();
     let semaphore = Arc::new(tokio::sync::Semaphore::new(cli.download_jobs.into()));
-    let handle = tokio::spawn(async move {
-        handle_all(remote, semaphore, &mut rx, deny_nolrc).await;
-    });
+    let handle = {
+        let records = records.clone();
+        tokio::spawn(async move {
+            handle_all(remote, semaphore, &mut rx, opts, records).await;
+        })
+    };
 
-    tokio::task::spawn_blocking(move || {
-        file::prepare_entries(&tx, &cli)
-            .expect("the amount of paths provided has to be verified at the cli level");
-    })
-    .await
-    .expect(JOIN_HANDLE_EXPECT_MSG);
+    let roots = if cli.from_stdin {
+        Vec::new()
+    } else {
+        cli.paths.clone()
+    };
+    {
+        let caches = Caches::new(
+            records.index.clone(),
+            records.progress.clone(),
+            records.output.clone(),
+        );
+        let prepared = tokio::task::spawn_blocking(move || {
+            if cli.from_stdin {
+                file::prepare_from_reader(&tx, &cli, io::stdin().lock(), caches);
+                Ok(())
+            } else {
+                file::prepare_entries(&tx, &cli, caches)
+            }
+        })
+        .await
+        .expect(JOIN_HANDLE_EXPECT_MSG);
+        if let Err(e) = prepared {
+            tracing::error!(%e);
+            output.error(e.to_string());
+        }
+    }
+    progress.finish_scan();
 
     handle.await.expect(JOIN_HANDLE_EXPECT_MSG);
+    if let Some(shown) = shown {
+        shown.finish();
+    }
+    let summary = output.finish();
+
+    if let Some(index) = &records.index {
+        if !opts.dry_run {
+            if let Err(e) = index.save(&roots).await {
+                tracing::warn!(%e, "failed to save index");
+            }
+        }
+    }
+    report_journal(records.journal.as_deref()).await;
+    summary
 }
 
-/// Handles all the given packs of data from `rx`. Will not create `.nolrc` files
-/// if `deny_nolrc` is `true`. Doesn't spawn any more jobs requesting lyrics from
-/// `remote` than `semaphore` has permits at one time
+/// Journal for the current run, unless journaling is disabled by `cli`
+fn open_journal(cli: &Cli) -> Option<Arc<Journal>> {
+    if cli.no_journal || cli.dry_run {
+        return None;
+    }
+
+    match cli.journal_dir.clone().or_else(journal::default_root) {
+        Some(root) => Some(Arc::new(Journal::new(&root, cli.keep_journals.into()))),
+        None => {
+            tracing::warn!("couldn't determine journal directory, changes won't be journaled");
+            None
+        }
+    }
+}
+
+/// Index of previous runs, unless it's disabled by `cli`
+fn open_index(cli: &Cli) -> Option<Arc<Index>> {
+    if cli.no_index {
+        return None;
+    }
+
+    match cli.index_file.clone().or_else(index::default_path) {
+        Some(path) => Some(Arc::new(Index::load(path, cli))),
+        None => {
+            tracing::warn!("couldn't determine index location, all tracks will be read");
+            None
+        }
+    }
+}
+
+/// Tells the user how to undo the current run, if anything was changed, and deletes journals of
+/// old runs
+async fn report_journal(journal: Option<&Journal>) {
+    if let Some(journal) = journal {
+        journal.prune().await;
+        if !journal.is_empty().await {
+            tracing::info!(
+                run_id = journal.run_id(),
+                "changes were journaled, run `{} undo {}` to revert them",
+                clap::crate_name!(),
+                journal.run_id(),
+            );
+        }
+    }
+}
+
+/// Parts of [`Cli`] that affect handling of every single entry
+#[derive(Clone, Copy, Debug)]
+struct EntryOpts {
+    /// Don't create `.nolrc` files
+    deny_nolrc: bool,
+    /// Mark instrumental tracks with `.lrc` files instead of `.nolrc` files
+    instrumental_lrc: bool,
+    /// Report changes to the filesystem instead of making them
+    dry_run: bool,
+    /// Report requests instead of sending them
+    offline: bool,
+}
+
+impl From<&Cli> for EntryOpts {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            deny_nolrc: cli.deny_nolrc,
+            instrumental_lrc: cli.instrumental_lrc,
+            dry_run: cli.dry_run,
+            offline: cli.offline,
+        }
+    }
+}
+
+/// Where results of handled tracks go, besides lyrics files
+#[derive(Clone, Debug, Default)]
+struct Records {
+    journal: Option<Arc<Journal>>,
+    index: Option<Arc<Index>>,
+    progress: Option<Arc<Progress>>,
+    output: Option<Arc<Output>>,
+}
+
+impl Records {
+    /// Records a track, that was handled with `handled`
+    fn handled(&self, pack: Packed, handled: Handled, opts: EntryOpts) {
+        let Packed {
+            path,
+            request,
+            sidecar,
+            stamp,
+        } = pack;
+        let outcome = handled.outcome;
+        if let Some(progress) = &self.progress {
+            progress.done(outcome);
+        }
+
+        if let Some(output) = &self.output {
+            let lyrics = match outcome {
+                Outcome::WrittenSynced | Outcome::WrittenPlain => Some(sidecar.lrc()),
+                Outcome::Instrumental if opts.instrumental_lrc => Some(sidecar.lrc()),
+                _ => None,
+            };
+            output.track(&output::Track {
+                path: path.clone(),
+                request: Some((&request).into()),
+                outcome,
+                lyrics,
+                lrclib_id: handled.lrclib_id,
+                error: handled.error,
+            });
+        }
+
+        if let (Some(index), Some(stamp)) = (&self.index, stamp) {
+            let entry = index_entry(stamp, request, &sidecar, outcome);
+            index.record(&path, entry);
+        }
+    }
+
+    /// Records a track, that couldn't be prepared for a request, or an error, that isn't related
+    /// to a single track
+    fn failed(&self, e: &PackError) {
+        tracing::warn!(%e);
+        let Some(path) = e.path() else {
+            if let Some(output) = &self.output {
+                output.error(e.to_string());
+            }
+            return;
+        };
+
+        if let Some(progress) = &self.progress {
+            progress.queued();
+            progress.done(Outcome::TagError);
+        }
+        if let Some(output) = &self.output {
+            output.track(&output::Track {
+                path: path.to_owned(),
+                request: None,
+                outcome: Outcome::TagError,
+                lyrics: None,
+                lrclib_id: None,
+                error: Some(e.to_string()),
+            });
+        }
+    }
+}
+
+/// Parts of a [`Pack`], that are needed once it's handled
+struct Packed {
+    path: PathBuf,
+    request: LyricsRequest,
+    sidecar: Sidecar,
+    stamp: Option<index::Stamp>,
+}
+
+/// Handles all the given packs of data from `rx`, according to `opts`. Doesn't spawn any more
+/// jobs requesting lyrics from `remote` than `semaphore` has permits at one time. Outcomes are
+/// recorded to `records`
 #[tracing::instrument(level = "trace", skip_all)]
 async fn handle_all<R>(
     remote: &'static R,
     semaphore: Arc<tokio::sync::Semaphore>,
     rx: &mut PacksRx,
-    deny_nolrc: bool,
+    opts: EntryOpts,
+    records: Records,
 ) where
     R: Remote + Sync,
 {
     let mut join_set = JoinSet::new();
 
     while let Some(res) = rx.recv().await {
-        if let Ok((request, path)) = res.inspect_err(|e| tracing::warn!(%e)) {
-            tracing::trace!(?request, ?path, "received new value");
-
-            let permit = semaphore.clone().acquire_owned();
+        let pack = match res {
+            Ok(pack) => pack,
+            Err(e) => {
+                records.failed(&e);
+                continue;
+            }
+        };
+        tracing::trace!(?pack, "received new value");
 
-            join_set.spawn(handle_entry(permit, remote, request, path, deny_nolrc));
+        let permit = {
+            let semaphore = semaphore.clone();
+            let progress = records.progress.clone();
+            async move {
+                let permit = semaphore.acquire_owned().await?;
+                Ok((permit, progress.map(|progress| progress.in_flight())))
+            }
+        };
+        if let Some(progress) = &records.progress {
+            progress.queued();
         }
+
+        let records = records.clone();
+        join_set.spawn(async move {
+            let packed = Packed {
+                path: pack.path.clone(),
+                request: pack.request.clone(),
+                sidecar: pack.sidecar.clone(),
+                stamp: pack.stamp,
+            };
+            let journal = records.journal.clone();
+            let handled = handle_entry(permit, remote, pack, opts, journal).await;
+            records.handled(packed, handled, opts);
+        });
     }
 
     join_set.join_all().await;
 }
 
+/// Index entry for a track, that ended up with `outcome`
+fn index_entry(
+    stamp: index::Stamp,
+    request: LyricsRequest,
+    sidecar: &Sidecar,
+    outcome: Outcome,
+) -> index::Entry {
+    let (lrc, nolrc) = match outcome {
+        Outcome::WrittenSynced | Outcome::WrittenPlain => (Some(sidecar.lrc()), None),
+        Outcome::NotFound => (None, Some(sidecar.nolrc())),
+        // Depends on --instrumental-lrc, whichever exists counts
+        Outcome::Instrumental => (Some(sidecar.lrc()), Some(sidecar.nolrc())),
+        _ => (None, None),
+    };
+    index::Entry {
+        stamp,
+        request,
+        outcome,
+        lrc,
+        nolrc,
+    }
+}
+
 #[tracing::instrument(level = "trace", skip_all)]
-async fn handle_entry<A, P, R>(
+async fn handle_entry<P, T, R>(
     permit: P,
     remote: &R,
-    request: LyricsRequest,
-    path: A,
-    deny_nolrc: bool,
-) where
-    A: AsRef<Path>,
-    P: Future<Output = Result<tokio::sync::OwnedSemaphorePermit, tokio::sync::AcquireError>>,
+    pack: Pack,
+    opts: EntryOpts,
+    journal: Option<Arc<Journal>>,
+) -> Handled
+where
+    P: Future<Output = Result<T, tokio::sync::AcquireError>>,
     R: Remote,
 {
-    let path = path.as_ref();
+    let Pack {
+        mut request,
+        mut alt_artists,
+        lrclib_id,
+        instrumental,
+        path,
+        sidecar,
+        stamp: _,
+    } = pack;
+    if let Some(instrumental) = instrumental {
+        let reason = instrumental.reason();
+        let journal = journal.as_deref();
+        // Guesses can be wrong, so they don't get an .lrc file, which is never retried
+        return match instrumental {
+            Instrumental::Override => {
+                write_instrumental(&path, &sidecar, NoLyrics::Local, reason, opts, journal).await
+            }
+            Instrumental::Guessed => {
+                write_nolrc(&path, &sidecar, NoLyrics::Local, reason, opts, journal).await
+            }
+        };
+    }
+    if opts.offline {
+        match lrclib_id {
+            Some(id) => plan::request_by_id(&path, id),
+            None => plan::request(&path, &request),
+        }
+        return Outcome::Planned.into();
+    }
+
     let permit = permit.await.expect("semaphore closed unexpectedly");
-    let response = remote.get_lyrics(&request).await;
+    let mut response = match lrclib_id {
+        Some(id) => {
+            alt_artists.clear(); // the id is exact, there's nothing else to try
+            remote.get_lyrics_by_id(id).await
+        }
+        None => remote.get_lyrics(&request).await,
+    };
+    for artist in alt_artists {
+        if !is_not_found(&response) {
+            break;
+        }
+        tracing::info!(
+            path = %path.display(),
+            artist,
+            "no lyrics found for \"{}\", retrying with a single artist",
+            request.artist,
+        );
+        request.artist = artist;
+        response = remote.get_lyrics(&request).await;
+    }
     drop(permit); // manually drop, since we're done bombarding the website with requests
 
-    match response {
-        Ok(
-            LyricsResponse {
-                synced_lyrics: Some(lyrics),
-                instrumental: Some(false) | None,
-                ..
-            }
-            | LyricsResponse {
-                plain_lyrics: Some(lyrics),
-                instrumental: Some(false) | None,
-                ..
-            },
-        ) => {
-            let mut path_owned = path.to_owned();
-            match replace_nolrc(&mut path_owned, &lyrics).await {
-                Ok(()) => {
-                    tracing::info!(path = %path.display(), "successfully replaced nolrc with lrc file");
-                }
-                Err(ReplaceNolrcError::Delete(e)) if e.kind() == io::ErrorKind::NotFound => {
-                    tracing::debug!(path = %path_owned.display(), "nolrc file not found");
-                }
-                Err(ReplaceNolrcError::Write(e)) => {
-                    tracing::warn!(%e, path = %path_owned.display(),"failed to write to lyrics file");
-                }
-                Err(ReplaceNolrcError::Delete(e)) => {
-                    tracing::warn!(%e, path = %path_owned.display(), "failed to delete existing nolrc file");
-                }
-            }
+    let lrclib_id = response.as_ref().ok().and_then(|response| response.id);
+    let handled = match response {
+        Ok(LyricsResponse {
+            instrumental: Some(true),
+            ..
+        }) => {
+            let reason = "instrumental according to LRCLIB";
+            let journal = journal.as_deref();
+            write_instrumental(
+                &path,
+                &sidecar,
+                NoLyrics::Instrumental,
+                reason,
+                opts,
+                journal,
+            )
+            .await
+        }
+        Ok(LyricsResponse {
+            synced_lyrics: Some(lyrics),
+            instrumental: Some(false) | None,
+            ..
+        }) => {
+            let reason = "synced lyrics found";
+            let journal = journal.as_deref();
+            write_lrc(&path, &sidecar, &lyrics, reason, opts, journal)
+                .await
+                .unwrap_or(Outcome::WrittenSynced.into())
+        }
+        Ok(LyricsResponse {
+            plain_lyrics: Some(lyrics),
+            instrumental: Some(false) | None,
+            ..
+        }) => {
+            let reason = "plain lyrics found";
+            let journal = journal.as_deref();
+            write_lrc(&path, &sidecar, &lyrics, reason, opts, journal)
+                .await
+                .unwrap_or(Outcome::WrittenPlain.into())
         }
 
-        // TODO: separate instrumental and tracks with no lyrics available (at this moment)
         Err(LyricsError::InvalidStatusCode {
             status: StatusCode::NOT_FOUND,
             url: _,
         })
         | Ok(_) => {
-            if !deny_nolrc {
-                // TODO (caching): save this info somewhere and don't try to attempt to get
-                // the song lyrics
-                tracing::info!(path = %path.display(), "no lyrics found");
-
-                let mut path_owned = path.to_owned();
-                match create_nolrc(&mut path_owned).await.map_err(|e| e.kind()) {
-                    Ok(_file) => {
-                        tracing::info!(path = %path.display(), "successfully created nolrc file");
-                    }
-                    Err(io::ErrorKind::AlreadyExists) => {
-                        tracing::debug!(path = %path_owned.display(), "skipping creation of nolrc file, since it exists");
-                    }
-                    Err(kind) => {
-                        tracing::warn!(path = %path_owned.display(), ?kind, "failed to create nolrc file");
-                    }
-                }
-            } else {
-                tracing::debug!(path = %path.display(), "not writing nolrc file");
-            }
+            let reason = "no lyrics found";
+            let journal = journal.as_deref();
+            write_nolrc(&path, &sidecar, NoLyrics::NotFound, reason, opts, journal).await
         }
 
         Err(e) => match e {
@@ -157,9 +480,143 @@ async fn handle_entry<A, P, R>(
                     e
                 )
             }
-            LyricsError::Misc(inner) => tracing::warn!(%inner),
-            LyricsError::InvalidStatusCode { .. } => tracing::warn!(%e),
+            LyricsError::Misc(inner) => {
+                tracing::warn!(%inner);
+                return Handled::failed(Outcome::NetworkError, inner.to_string());
+            }
+            LyricsError::InvalidStatusCode { .. } => {
+                tracing::warn!(%e);
+                return Handled::failed(Outcome::NetworkError, e.to_string());
+            }
         },
+    };
+
+    Handled {
+        lrclib_id,
+        ..handled
+    }
+}
+
+/// Whether LRCLIB doesn't know the requested track at all
+fn is_not_found(response: &crate::remote::Result) -> bool {
+    matches!(
+        response,
+        Err(LyricsError::InvalidStatusCode {
+            status: StatusCode::NOT_FOUND,
+            url: _,
+        })
+    )
+}
+
+/// Writes `lyrics` for the track at `path`. Returns an outcome only if nothing was written,
+/// otherwise it's up to the caller, what kind of lyrics were written
+async fn write_lrc(
+    path: &Path,
+    sidecar: &Sidecar,
+    lyrics: &str,
+    reason: &str,
+    opts: EntryOpts,
+    journal: Option<&Journal>,
+) -> Option<Handled> {
+    if opts.dry_run {
+        plan::replace_nolrc(sidecar, reason).await;
+        return Some(Outcome::Planned.into());
+    }
+
+    let err = match replace_nolrc(sidecar, lyrics, journal).await {
+        Ok(()) => {
+            tracing::info!(path = %path.display(), "successfully wrote lrc file");
+            return None;
+        }
+        Err(err) => err,
+    };
+    match &err {
+        ReplaceNolrcError::Write(e) => {
+            tracing::warn!(%e, path = %sidecar.lrc().display(), "failed to write to lyrics file");
+        }
+        ReplaceNolrcError::Delete(e) => {
+            tracing::warn!(%e, path = %sidecar.nolrc().display(), "failed to delete existing nolrc file");
+        }
+        ReplaceNolrcError::Backup(e) => {
+            tracing::warn!(%e, path = %sidecar.lrc().display(), "failed to back up existing lyrics file");
+        }
+    }
+    Some(Handled::failed(Outcome::WriteError, err.to_string()))
+}
+
+/// Marks the track at `path` as instrumental, with an `.lrc` file, if asked to, or a `.nolrc`
+/// file
+async fn write_instrumental(
+    path: &Path,
+    sidecar: &Sidecar,
+    no_lyrics: NoLyrics,
+    reason: &str,
+    opts: EntryOpts,
+    journal: Option<&Journal>,
+) -> Handled {
+    if opts.instrumental_lrc {
+        write_lrc(path, sidecar, INSTRUMENTAL_LRC, reason, opts, journal)
+            .await
+            .unwrap_or(Outcome::Instrumental.into())
+    } else {
+        write_nolrc(path, sidecar, no_lyrics, reason, opts, journal).await
+    }
+}
+
+/// Creates a `.nolrc` file, that records why the track at `path` has no lyrics. An existing one
+/// is updated, if the reason changed
+async fn write_nolrc(
+    path: &Path,
+    sidecar: &Sidecar,
+    no_lyrics: NoLyrics,
+    reason: &str,
+    opts: EntryOpts,
+    journal: Option<&Journal>,
+) -> Handled {
+    let outcome = match no_lyrics {
+        NoLyrics::NotFound => Outcome::NotFound,
+        NoLyrics::Instrumental | NoLyrics::Local => Outcome::Instrumental,
+    };
+    if opts.deny_nolrc {
+        tracing::debug!(path = %path.display(), "not writing nolrc file");
+        return outcome.into();
+    }
+
+    // TODO (caching): save this info somewhere and don't try to attempt to get
+    // the song lyrics
+    tracing::info!(path = %path.display(), "{}", reason);
+    if opts.dry_run {
+        plan::create_nolrc(sidecar, no_lyrics, reason).await;
+        return Outcome::Planned.into();
+    }
+
+    match create_nolrc(sidecar, no_lyrics, journal).await {
+        Ok(()) => {
+            tracing::info!(path = %path.display(), "successfully created nolrc file");
+            outcome.into()
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            if NoLyrics::read_async(&sidecar.nolrc()).await == no_lyrics {
+                tracing::debug!(path = %sidecar.nolrc().display(), "skipping creation of nolrc file, since it exists");
+                return outcome.into();
+            }
+            match update_nolrc(sidecar, no_lyrics, journal).await {
+                Ok(()) => {
+                    tracing::info!(path = %path.display(), "successfully updated nolrc file");
+                    outcome.into()
+                }
+                Err(e) => {
+                    tracing::warn!(%e, path = %sidecar.nolrc().display(), "failed to update nolrc file");
+                    let error = format!("failed to update nolrc file: {}", e);
+                    Handled::failed(Outcome::WriteError, error)
+                }
+            }
+        }
+        Err(e) => {
+            tracing::warn!(path = %sidecar.nolrc().display(), kind = ?e.kind(), "failed to create nolrc file");
+            let error = format!("failed to create nolrc file: {}", e);
+            Handled::failed(Outcome::WriteError, error)
+        }
     }
 }
 
@@ -169,33 +626,131 @@ enum ReplaceNolrcError {
     Write(#[source] io::Error),
     #[error("failed to delete nolrc file due to error: \"{0}\"")]
     Delete(#[source] io::Error),
+    #[error("failed to back up existing lyrics file due to error: \"{0}\"")]
+    Backup(#[source] io::Error),
 }
 
+/// Writes `lyrics` to the `.lrc` file and deletes the `.nolrc` file as a single transaction.
+/// Lyrics are written to a temporary file first, which is then renamed, so the `.lrc` file
+/// is never left half-written. If anything fails along the way, the `.nolrc` file is put back.
+/// Changes are recorded to `journal`, if any
 #[tracing::instrument(level = "trace", skip_all)]
-async fn replace_nolrc<C>(path: &mut PathBuf, lyrics: C) -> Result<(), ReplaceNolrcError>
+async fn replace_nolrc<C>(
+    sidecar: &Sidecar,
+    lyrics: C,
+    journal: Option<&Journal>,
+) -> Result<(), ReplaceNolrcError>
 where
     C: AsRef<[u8]>,
 {
-    path.set_extension("lrc");
-    tokio::fs::write(&path, &lyrics)
+    let lrc = sidecar.lrc();
+    let nolrc = sidecar.nolrc();
+
+    let staged = atomic::stage(&lrc, lyrics.as_ref().to_vec())
         .await
         .map_err(ReplaceNolrcError::Write)?;
 
-    path.set_extension("nolrc");
-    tokio::fs::remove_file(&path)
+    // Changes that can't be undone aren't made at all
+    let (lrc_backup, nolrc_backup) = match journal {
+        Some(journal) => {
+            let lrc_backup = journal.backup(&lrc).await;
+            let nolrc_backup = journal.backup(&nolrc).await;
+            (
+                lrc_backup.map_err(ReplaceNolrcError::Backup)?,
+                nolrc_backup.map_err(ReplaceNolrcError::Backup)?,
+            )
+        }
+        None => (None, None),
+    };
+
+    // If we crash after this point, we're left with neither .lrc nor .nolrc file, which is fine:
+    // lyrics will be requested again on the next run. The .nolrc file stays behind as a
+    // temporary file, that's deleted by `clean`
+    let nolrc_aside = atomic::set_aside(&nolrc)
         .await
         .map_err(ReplaceNolrcError::Delete)?;
 
+    if let Err(e) = atomic::commit(staged, &lrc).await {
+        if let Some(nolrc_aside) = nolrc_aside {
+            if let Err(e) = atomic::restore(nolrc_aside, &nolrc).await {
+                tracing::warn!(%e, path = %nolrc.display(), "failed to restore nolrc file");
+            }
+        }
+        return Err(ReplaceNolrcError::Write(e));
+    }
+
+    if let Some(journal) = journal {
+        let entry = match lrc_backup {
+            Some(backup) => Entry::Overwrite { path: lrc, backup },
+            None => Entry::Create { path: lrc },
+        };
+        record(journal, &entry).await;
+
+        if let (Some(_), Some(backup)) = (&nolrc_aside, nolrc_backup) {
+            let entry = Entry::Delete {
+                path: nolrc,
+                backup,
+            };
+            record(journal, &entry).await;
+        }
+    }
+
     Ok(())
 }
 
-#[tracing::instrument(level = "trace")]
-async fn create_nolrc(path: &mut PathBuf) -> Result<tokio::fs::File, io::Error> {
-    path.set_extension("nolrc");
-    tokio::fs::OpenOptions::new()
+#[tracing::instrument(level = "trace", skip(journal))]
+async fn create_nolrc(
+    sidecar: &Sidecar,
+    no_lyrics: NoLyrics,
+    journal: Option<&Journal>,
+) -> Result<(), io::Error> {
+    let path = sidecar.nolrc();
+    let mut file = tokio::fs::OpenOptions::new()
         .create_new(true)
         .write(true)
         .truncate(false)
         .open(&path)
-        .await
+        .await?;
+
+    // The file is there already, so it has to be recorded, even if the reason can't be written
+    if let Some(journal) = journal {
+        record(journal, &Entry::Create { path }).await;
+    }
+
+    file.write_all(no_lyrics.marker().as_bytes()).await?;
+    file.flush().await
+}
+
+/// Replaces the reason in an existing `.nolrc` file
+#[tracing::instrument(level = "trace", skip(journal))]
+async fn update_nolrc(
+    sidecar: &Sidecar,
+    no_lyrics: NoLyrics,
+    journal: Option<&Journal>,
+) -> Result<(), io::Error> {
+    let path = sidecar.nolrc();
+    let staged = atomic::stage(&path, no_lyrics.marker().into_bytes()).await?;
+    let backup = match journal {
+        Some(journal) => journal.backup(&path).await?,
+        None => None,
+    };
+    atomic::commit(staged, &path).await?;
+
+    if let Some(journal) = journal {
+        let entry = match backup {
+            Some(backup) => Entry::Overwrite { path, backup },
+            None => Entry::Create { path },
+        };
+        record(journal, &entry).await;
+    }
+
+    Ok(())
+}
+
+/// Records a change that was already made. There's no point in failing at this point, so
+/// errors are only reported
+async fn record(journal: &Journal, entry: &Entry) {
+    if let Err(e) = journal.record(entry).await {
+        tracing::warn!(%e, ?entry, "failed to record change to the journal");
+    }
 }
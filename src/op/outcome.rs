@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// Outcome of handling a single track, along with the details worth reporting
+#[derive(Debug)]
+pub struct Handled {
+    pub outcome: Outcome,
+    /// LRCLIB id of the received lyrics
+    pub lrclib_id: Option<u64>,
+    /// Why the track failed, if it did
+    pub error: Option<String>,
+}
+
+impl From<Outcome> for Handled {
+    fn from(outcome: Outcome) -> Self {
+        Self {
+            outcome,
+            lrclib_id: None,
+            error: None,
+        }
+    }
+}
+
+impl Handled {
+    pub fn failed(outcome: Outcome, error: String) -> Self {
+        Self {
+            outcome,
+            lrclib_id: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// What happened to a single track
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// Synced lyrics were written to the `.lrc` file
+    WrittenSynced,
+    /// Plain lyrics were written to the `.lrc` file
+    WrittenPlain,
+    /// There are no lyrics for the track, a `.nolrc` file was created unless denied
+    NotFound,
+    /// The track is instrumental, a `.nolrc` file, or an `.lrc` file with `--instrumental-lrc`,
+    /// was created unless denied
+    Instrumental,
+    /// Lyrics weren't requested, since the track already has lyrics files
+    SkippedExisting,
+    /// Tags couldn't be read, or the track couldn't be prepared for a request otherwise
+    TagError,
+    /// Lyrics couldn't be requested
+    NetworkError,
+    /// Lyrics were received, but couldn't be written
+    WriteError,
+    /// Nothing was changed, because of `--dry-run` or `--offline`
+    Planned,
+}
+
+impl Outcome {
+    pub const ALL: [Self; 9] = [
+        Self::WrittenSynced,
+        Self::WrittenPlain,
+        Self::NotFound,
+        Self::Instrumental,
+        Self::SkippedExisting,
+        Self::TagError,
+        Self::NetworkError,
+        Self::WriteError,
+        Self::Planned,
+    ];
+
+    /// Whether the track failed, and is worth another try
+    pub fn is_failure(self) -> bool {
+        matches!(self, Self::TagError | Self::NetworkError | Self::WriteError)
+    }
+
+    /// Short description for status lines
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::WrittenSynced => "synced",
+            Self::WrittenPlain => "plain",
+            Self::NotFound => "not found",
+            Self::Instrumental => "instrumental",
+            Self::SkippedExisting => "skipped",
+            Self::TagError => "tag errors",
+            Self::NetworkError => "network errors",
+            Self::WriteError => "write errors",
+            Self::Planned => "planned",
+        }
+    }
+}
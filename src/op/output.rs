@@ -0,0 +1,163 @@
+use super::outcome::Outcome;
+use super::progress::suspend;
+use super::summary::{Failure, Summary};
+use crate::cli::OutputFormat;
+use crate::remote::LyricsRequest;
+use core::fmt;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// What happened to a single track, as reported with `--output json`
+#[derive(Debug, Serialize)]
+pub struct Track {
+    pub path: PathBuf,
+    /// What was, or would've been, requested. Unknown for tracks, that were skipped before their
+    /// tags were read
+    pub request: Option<Request>,
+    pub outcome: Outcome,
+    /// Lyrics file of the track, if it has one
+    pub lyrics: Option<PathBuf>,
+    /// LRCLIB id of the lyrics, that were received or pinned by overrides
+    pub lrclib_id: Option<u64>,
+    /// Why the track failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Fields of a [`LyricsRequest`]
+#[derive(Debug, Serialize)]
+pub struct Request {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    /// In seconds
+    pub duration: Option<f64>,
+}
+
+impl From<&LyricsRequest> for Request {
+    fn from(request: &LyricsRequest) -> Self {
+        Self {
+            artist: request.artist.clone(),
+            title: request.title.clone(),
+            album: request.album.clone(),
+            duration: request.duration.map(|d| d.as_secs_f64()),
+        }
+    }
+}
+
+/// Single line of `--output json`
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Line<'a> {
+    Track(&'a Track),
+    Summary {
+        total: usize,
+        outcomes: serde_json::Map<String, serde_json::Value>,
+        failed: &'a [Failure],
+        errors: &'a [String],
+    },
+}
+
+/// Where reports of tracks go. They're put together into a [`Summary`], that's printed at the end.
+/// With `--output json`, every track is also printed as a JSON object on its own line
+pub struct Output {
+    format: OutputFormat,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    out: Box<dyn Write + Send>,
+    summary: Summary,
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Output")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Output {
+    /// Output to stdout
+    pub fn new(format: OutputFormat) -> Self {
+        Self::to(format, std::io::stdout())
+    }
+
+    pub fn to<W>(format: OutputFormat, out: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            format,
+            inner: Mutex::new(Inner {
+                out: Box::new(out),
+                summary: Summary::default(),
+            }),
+        }
+    }
+
+    pub fn track(&self, track: &Track) {
+        let mut inner = self.lock();
+        inner.summary.record(track);
+        if let OutputFormat::Json = self.format {
+            inner.print(&Line::Track(track));
+        }
+    }
+
+    /// Reports an error, that isn't related to a single track
+    pub fn error(&self, error: String) {
+        self.lock().summary.errors.push(error);
+    }
+
+    /// Prints the summary of all the reported tracks and returns it
+    pub fn finish(&self) -> Summary {
+        let mut inner = self.lock();
+        let summary = std::mem::take(&mut inner.summary);
+        match self.format {
+            OutputFormat::Text => inner.write(format!("{}\n", summary).into_bytes()),
+            OutputFormat::Json => {
+                let outcomes = Outcome::ALL
+                    .iter()
+                    .map(|&outcome| {
+                        let name = serde_json::to_value(outcome)
+                            .ok()
+                            .and_then(|name| name.as_str().map(str::to_owned))
+                            .expect("outcomes are serialized as strings");
+                        (name, summary.count(outcome).into())
+                    })
+                    .collect();
+                inner.print(&Line::Summary {
+                    total: summary.total(),
+                    outcomes,
+                    failed: &summary.failed,
+                    errors: &summary.errors,
+                });
+            }
+        }
+        summary
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .expect("thread panicked while holding the lock")
+    }
+}
+
+impl Inner {
+    fn print(&mut self, line: &Line) {
+        let mut json = serde_json::to_vec(line).expect("reports are always serializable");
+        json.push(b'\n');
+        self.write(json);
+    }
+
+    fn write(&mut self, buf: Vec<u8>) {
+        let out = &mut self.out;
+        if let Err(e) = suspend(|| out.write_all(&buf).and_then(|()| out.flush())) {
+            tracing::warn!(%e, "failed to write report");
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use super::listing::Listings;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Name of the override file, that applies to all the tracks in its directory
+const DIR_FILE: &str = ".cnova.toml";
+/// Suffix of override files for single tracks, appended to the track stem
+const TRACK_SUFFIX: &str = ".cnova.toml";
+
+/// Manual overrides of what's requested for a track. Read from `.cnova.toml` in the track
+/// directory and from `<stem>.cnova.toml` next to the track. The directory file can also contain
+/// `[tracks."<file name>"]` tables with overrides for single tracks
+///
+/// ```toml
+/// album = "Abbey Road"
+///
+/// [tracks."01 - Come Together.flac"]
+/// title = "Come Together - Remastered 2009"
+/// duration = 259
+///
+/// [tracks."05 - Interlude.flac"]
+/// skip = true
+/// ```
+///
+/// Values for single tracks take precedence over the values for the directory. The title,
+/// duration and LRCLIB id can't be set for the whole directory
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    /// Duration of the track in seconds
+    pub duration: Option<u64>,
+    /// Don't request lyrics for the track at all
+    pub skip: Option<bool>,
+    /// Fetch lyrics with this LRCLIB id instead of searching by tags
+    pub lrclib_id: Option<u64>,
+    /// The track has no lyrics, don't request them. `false` disables guessing for the track
+    pub instrumental: Option<bool>,
+    /// Overrides for single tracks, keyed by their file names or stems. Only used in the
+    /// directory file
+    #[serde(default)]
+    tracks: HashMap<String, Override>,
+}
+
+impl Override {
+    pub fn skip(&self) -> bool {
+        self.skip.unwrap_or(false)
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration.map(Duration::from_secs)
+    }
+
+    /// Name of a value, that only makes sense for a single track, if there's one
+    fn track_only(&self) -> Option<&'static str> {
+        if self.title.is_some() {
+            Some("title")
+        } else if self.duration.is_some() {
+            Some("duration")
+        } else if self.lrclib_id.is_some() {
+            Some("lrclib_id")
+        } else {
+            None
+        }
+    }
+
+    /// Fills in values missing in `self` from `fallback`
+    fn or(self, fallback: &Self) -> Self {
+        Self {
+            artist: self.artist.or_else(|| fallback.artist.clone()),
+            title: self.title.or_else(|| fallback.title.clone()),
+            album: self.album.or_else(|| fallback.album.clone()),
+            duration: self.duration.or(fallback.duration),
+            skip: self.skip.or(fallback.skip),
+            lrclib_id: self.lrclib_id.or(fallback.lrclib_id),
+            instrumental: self.instrumental.or(fallback.instrumental),
+            tracks: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OverrideError {
+    #[error(transparent)]
+    Read(#[from] std::io::Error),
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+    #[error("`{0}` can't apply to a whole directory, set it in a [tracks.\"<file name>\"] table")]
+    TrackOnly(&'static str),
+    #[error("`{0}` can't apply to every track of a CUE sheet, set it for a single track instead")]
+    CueTrackOnly(&'static str),
+}
+
+type Loaded = Result<Arc<Override>, Arc<OverrideError>>;
+
+/// Cache of directory override files. Each of them is read only once, the first time it's
+/// requested
+#[derive(Debug, Default)]
+pub struct Overrides {
+    dirs: Mutex<HashMap<PathBuf, Loaded>>,
+}
+
+impl Overrides {
+    /// Overrides for the track at `path`. Returns the path to the offending file along with the
+    /// error, if any of the override files couldn't be read
+    pub fn get(
+        &self,
+        path: &Path,
+        listings: &Listings,
+    ) -> Result<Override, (PathBuf, Arc<OverrideError>)> {
+        let (Some(dir), Some(name), Some(stem)) =
+            (path.parent(), path.file_name(), path.file_stem())
+        else {
+            return Ok(Override::default());
+        };
+
+        let (single, dir_override) = self.single(dir, &[name, stem], listings)?;
+        Ok(single.or(&dir_override))
+    }
+
+    /// Overrides for a single CUE track of the file at `path`. They're keyed by `track_stem`,
+    /// the name of the lyrics files of the track, e.g. `[tracks."album.01"]` or
+    /// `album.01.cnova.toml`, and take precedence over the overrides for the whole file
+    pub fn get_cue_track(
+        &self,
+        path: &Path,
+        track_stem: &OsStr,
+        listings: &Listings,
+    ) -> Result<Override, (PathBuf, Arc<OverrideError>)> {
+        let whole = self.get(path, listings)?;
+        if let Some(key) = whole.track_only() {
+            return Err((path.to_owned(), Arc::new(OverrideError::CueTrackOnly(key))));
+        }
+        let Some(dir) = path.parent() else {
+            return Ok(whole);
+        };
+
+        let (single, _) = self.single(dir, &[track_stem], listings)?;
+        Ok(single.or(&whole))
+    }
+
+    /// Overrides for a single track in `dir`, looked up in the directory file by the first of
+    /// `keys` that's there and in the track file named after the last of them. The overrides
+    /// for the whole directory are returned as well
+    fn single(
+        &self,
+        dir: &Path,
+        keys: &[&OsStr],
+        listings: &Listings,
+    ) -> Result<(Override, Arc<Override>), (PathBuf, Arc<OverrideError>)> {
+        let dir_file = dir.join(DIR_FILE);
+        let dir_override = self.dir(&dir_file, listings).map_err(|e| (dir_file, e))?;
+        let for_track = keys
+            .iter()
+            .find_map(|key| dir_override.tracks.get(&*key.to_string_lossy()));
+
+        let mut track_file = keys.last().copied().unwrap_or_default().to_owned();
+        track_file.push(TRACK_SUFFIX);
+        let track_file = dir.join(track_file);
+        let track_override = match listings.find(&track_file) {
+            Some(track_file) => load(&track_file).map_err(|e| (track_file, Arc::new(e)))?,
+            None => Override::default(),
+        };
+        if !track_override.tracks.is_empty() {
+            tracing::warn!(
+                path = %track_file.display(),
+                "tracks are only supported in {}, ignoring them",
+                DIR_FILE,
+            );
+        }
+
+        let res = match for_track {
+            Some(for_track) => track_override.or(for_track),
+            None => track_override,
+        };
+        Ok((res, dir_override))
+    }
+
+    /// Whether there are any override files for the track at `path`
+    pub fn exist(&self, path: &Path, listings: &Listings) -> bool {
+        let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+            return false;
+        };
+        let mut track_file = OsString::from(stem);
+        track_file.push(TRACK_SUFFIX);
+
+        listings.find(&dir.join(DIR_FILE)).is_some()
+            || listings.find(&path.with_file_name(track_file)).is_some()
+    }
+
+    fn dir(&self, dir_file: &Path, listings: &Listings) -> Loaded {
+        if let Some(loaded) = self.lock().get(dir_file) {
+            return loaded.clone();
+        }
+
+        let loaded = match listings.find(dir_file) {
+            Some(path) => load(&path)
+                .and_then(|over| match over.track_only() {
+                    Some(key) => Err(OverrideError::TrackOnly(key)),
+                    None => Ok(over),
+                })
+                .map(Arc::new)
+                .map_err(Arc::new),
+            None => Ok(Arc::default()),
+        };
+        self.lock()
+            .entry(dir_file.to_owned())
+            .or_insert(loaded)
+            .clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Loaded>> {
+        self.dirs
+            .lock()
+            .expect("walker thread panicked while holding the lock")
+    }
+}
+
+fn load(path: &Path) -> Result<Override, OverrideError> {
+    let res = toml::from_str(&std::fs::read_to_string(path)?)?;
+    tracing::debug!(path = %path.display(), "read overrides");
+    Ok(res)
+}
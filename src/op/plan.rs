@@ -0,0 +1,104 @@
+use super::file::Sidecar;
+use super::marker::NoLyrics;
+use super::progress::suspend;
+use crate::remote::LyricsRequest;
+use core::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A single change to the filesystem, that would've been made, if not for `--dry-run`
+#[derive(Debug)]
+pub enum Action<'a> {
+    CreateLrc(&'a Path),
+    OverwriteLrc(&'a Path),
+    CreateNolrc(&'a Path),
+    /// The reason in an existing `.nolrc` file changes
+    UpdateNolrc(&'a Path),
+    DeleteNolrc(&'a Path),
+    Delete(&'a Path),
+}
+
+impl fmt::Display for Action<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (what, path) = match self {
+            Self::CreateLrc(path) => ("create .lrc", path),
+            Self::OverwriteLrc(path) => ("overwrite .lrc", path),
+            Self::CreateNolrc(path) => ("create .nolrc", path),
+            Self::UpdateNolrc(path) => ("update .nolrc", path),
+            Self::DeleteNolrc(path) => ("delete .nolrc", path),
+            Self::Delete(path) => ("delete", path),
+        };
+        write!(f, "{}: {}", what, path.display())
+    }
+}
+
+/// Whether plans go to stderr, so that they don't get mixed with `--output json`
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Prints plans to stderr from now on
+pub fn to_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+fn print(line: fmt::Arguments) {
+    suspend(|| {
+        if TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    });
+}
+
+pub fn report(action: Action, reason: &str) {
+    print(format_args!("{} ({})", action, reason));
+}
+
+/// Reports what [`super::replace_nolrc`] would do
+pub async fn replace_nolrc(sidecar: &Sidecar, reason: &str) {
+    let lrc = sidecar.lrc();
+    if exists(&lrc).await {
+        report(Action::OverwriteLrc(&lrc), reason);
+    } else {
+        report(Action::CreateLrc(&lrc), reason);
+    }
+
+    let nolrc = sidecar.nolrc();
+    if exists(&nolrc).await {
+        report(Action::DeleteNolrc(&nolrc), reason);
+    }
+}
+
+/// Reports what [`super::create_nolrc`] or [`super::update_nolrc`] would do for a track with
+/// `no_lyrics`
+pub async fn create_nolrc(sidecar: &Sidecar, no_lyrics: NoLyrics, reason: &str) {
+    let nolrc = sidecar.nolrc();
+    if !exists(&nolrc).await {
+        report(Action::CreateNolrc(&nolrc), reason);
+    } else if NoLyrics::read_async(&nolrc).await != no_lyrics {
+        report(Action::UpdateNolrc(&nolrc), reason);
+    }
+}
+
+/// Reports a request that would've been sent, if not for `--offline`
+pub fn request(path: &Path, request: &LyricsRequest) {
+    print(format_args!(
+        "request lyrics: {} (artist \"{}\", title \"{}\")",
+        path.display(),
+        request.artist,
+        request.title,
+    ));
+}
+
+/// Reports a request by a pinned LRCLIB id, that would've been sent, if not for `--offline`
+pub fn request_by_id(path: &Path, id: u64) {
+    print(format_args!(
+        "request lyrics: {} (LRCLIB id {})",
+        path.display(),
+        id
+    ));
+}
+
+async fn exists(path: &Path) -> bool {
+    tokio::fs::try_exists(path).await.unwrap_or(false)
+}
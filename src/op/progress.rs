@@ -0,0 +1,220 @@
+use super::outcome::Outcome;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{IsTerminal as _, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// How often the progress bar is redrawn
+const TICK: Duration = Duration::from_millis(100);
+/// How often a status line is printed, when stderr isn't a terminal
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Progress bar, that's currently shown, if any. Everything printed to the terminal has to go
+/// through [`suspend`], so that the bar isn't torn apart
+static BAR: RwLock<Option<ProgressBar>> = RwLock::new(None);
+
+/// Counters of a single run, updated as tracks are found and handled
+#[derive(Debug)]
+pub struct Progress {
+    started: Instant,
+    /// Files looked at, whether they turned out to be tracks or not
+    scanned: AtomicUsize,
+    /// Tracks sent to be handled
+    queued: AtomicUsize,
+    /// Requests holding a download permit
+    in_flight: AtomicUsize,
+    done: Mutex<HashMap<Outcome, usize>>,
+    /// All the tracks are known, so the ETA makes sense
+    scan_finished: AtomicBool,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            scanned: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            done: Mutex::new(HashMap::new()),
+            scan_finished: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Progress {
+    pub fn scanned(&self) {
+        self.scanned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn finish_scan(&self) {
+        self.scan_finished.store(true, Ordering::Relaxed);
+    }
+
+    pub fn done(&self, outcome: Outcome) {
+        *self.lock().entry(outcome).or_default() += 1;
+    }
+
+    /// How many tracks ended up with each outcome so far
+    pub fn outcomes(&self) -> HashMap<Outcome, usize> {
+        self.lock().clone()
+    }
+
+    /// Counts a request as in flight, until the returned guard is dropped
+    pub fn in_flight(self: &Arc<Self>) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(self.clone())
+    }
+
+    /// Single line describing the progress, e.g.
+    /// `120 files scanned, 40/100 tracks done, 5 requests in flight (synced 30, not found 10), 4.0 tracks/s, ETA 15s`
+    pub fn status(&self) -> String {
+        let queued = self.queued.load(Ordering::Relaxed);
+        let outcomes = self.outcomes();
+        let done: usize = outcomes.values().sum();
+
+        let mut res = format!(
+            "{} files scanned, {}/{} tracks done, {} requests in flight",
+            self.scanned.load(Ordering::Relaxed),
+            done,
+            queued,
+            self.in_flight.load(Ordering::Relaxed),
+        );
+
+        let counts: Vec<_> = Outcome::ALL
+            .iter()
+            .filter_map(|outcome| Some((outcome.label(), *outcomes.get(outcome)?)))
+            .map(|(label, count)| format!("{} {}", label, count))
+            .collect();
+        if !counts.is_empty() {
+            let _ = write!(res, " ({})", counts.join(", "));
+        }
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = done as f64 / elapsed;
+        if done > 0 {
+            let _ = write!(res, ", {:.1} tracks/s", rate);
+        }
+        if self.scan_finished.load(Ordering::Relaxed) && done > 0 {
+            let remaining = queued.saturating_sub(done) as f64 / rate;
+            let eta = Duration::from_secs(remaining.ceil() as u64);
+            let _ = write!(res, ", ETA {}", indicatif::HumanDuration(eta));
+        }
+
+        res
+    }
+
+    /// Keeps showing the progress until [`Shown::finish`]. Draws a progress bar, if stderr is a
+    /// terminal, otherwise prints a status line every once in a while
+    pub fn show(self: &Arc<Self>) -> Shown {
+        let progress = self.clone();
+        if !std::io::stderr().is_terminal() {
+            let task = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(STATUS_INTERVAL);
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    eprintln!("{}", progress.status());
+                }
+            });
+            return Shown { task, bar: None };
+        }
+
+        let style = ProgressStyle::with_template("{spinner} [{bar:30}] {wide_msg}")
+            .expect("progress bar template is valid")
+            .progress_chars("=> ");
+        let bar = ProgressBar::new(0).with_style(style);
+        *BAR.write().expect(BAR_LOCK_EXPECT_MSG) = Some(bar.clone());
+
+        let task = {
+            let bar = bar.clone();
+            tokio::spawn(async move {
+                loop {
+                    bar.set_length(progress.queued.load(Ordering::Relaxed) as u64);
+                    bar.set_position(progress.outcomes().values().sum::<usize>() as u64);
+                    bar.set_message(progress.status());
+                    tokio::time::sleep(TICK).await;
+                }
+            })
+        };
+        Shown {
+            task,
+            bar: Some(bar),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Outcome, usize>> {
+        self.done
+            .lock()
+            .expect("thread panicked while holding the lock")
+    }
+}
+
+/// Request in flight, see [`Progress::in_flight`]
+#[derive(Debug)]
+pub struct InFlight(Arc<Progress>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Progress, that's being shown, see [`Progress::show`]
+#[derive(Debug)]
+pub struct Shown {
+    task: tokio::task::JoinHandle<()>,
+    bar: Option<ProgressBar>,
+}
+
+impl Shown {
+    /// Stops showing the progress and clears the progress bar
+    pub fn finish(self) {
+        self.task.abort();
+        if let Some(bar) = self.bar {
+            *BAR.write().expect(BAR_LOCK_EXPECT_MSG) = None;
+            bar.finish_and_clear();
+        }
+    }
+}
+
+const BAR_LOCK_EXPECT_MSG: &str = "thread panicked while holding the progress bar lock";
+
+/// Runs `f`, that prints to the terminal, with the progress bar hidden
+pub fn suspend<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let bar = BAR.read().expect(BAR_LOCK_EXPECT_MSG).clone();
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
+
+/// Writer of log lines to stderr, that doesn't tear the progress bar. Every line is buffered and
+/// written at once, when the writer is dropped
+#[derive(Debug, Default)]
+pub struct LogWriter(Vec<u8>);
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        let _ = suspend(|| std::io::stderr().write_all(&self.0));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::cli::WalkFilters;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Decides which entries are skipped, according to [`WalkFilters`]
+#[derive(Clone, Debug)]
+pub struct Prune {
+    include: Globs,
+    exclude: Globs,
+    markers: Vec<String>,
+}
+
+/// Globs split by what they're matched against
+#[derive(Clone, Debug)]
+struct Globs {
+    /// Globs without a separator, matched against file names
+    names: GlobSet,
+    /// Globs with a separator, matched against whole paths
+    paths: GlobSet,
+}
+
+impl Prune {
+    pub fn new(filters: &WalkFilters) -> Self {
+        Self {
+            include: Globs::new(&filters.include),
+            exclude: Globs::new(&filters.exclude),
+            markers: filters.skip_marker.clone(),
+        }
+    }
+
+    /// Whether the walker should keep the entry at `path`. Files have to match --include, if
+    /// there's any. Directories are checked for marker files
+    pub fn keeps(&self, path: &Path, is_dir: bool) -> bool {
+        if self.exclude.matches(path) {
+            tracing::debug!(path = %path.display(), "excluded by a glob");
+            return false;
+        }
+
+        if is_dir {
+            let marker = self
+                .markers
+                .iter()
+                .find(|marker| path.join(marker).is_file());
+            if let Some(marker) = marker {
+                tracing::info!(path = %path.display(), marker, "skipping a directory with a marker file");
+                return false;
+            }
+            true
+        } else {
+            self.include.is_empty() || self.include.matches(path)
+        }
+    }
+
+    /// Same as [`Self::keeps`], but for an entry, that wasn't found by walking. Directories it's
+    /// in are checked as well, up to `root`
+    pub fn keeps_nested(&self, path: &Path, is_dir: bool, root: &Path) -> bool {
+        self.keeps(path, is_dir)
+            && path
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(root))
+                .all(|dir| self.keeps(dir, true))
+    }
+}
+
+impl Globs {
+    fn new(globs: &[Glob]) -> Self {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for glob in globs {
+            if glob.glob().contains('/') {
+                paths.add(glob.clone());
+            } else {
+                names.add(glob.clone());
+            }
+        }
+
+        Self {
+            names: names.build().expect("globs are verified at the cli level"),
+            paths: paths.build().expect("globs are verified at the cli level"),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.paths.is_empty()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|name| self.names.is_match(name))
+            || self.paths.is_match(path)
+    }
+}
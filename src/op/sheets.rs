@@ -0,0 +1,68 @@
+use super::listing::Listings;
+use crate::cue::CueSheet;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Cache of CUE sheets. All the sheets in a directory are read only once, the first time a track
+/// from that directory is looked up
+#[derive(Debug, Default)]
+pub struct CueSheets {
+    dirs: Mutex<HashMap<PathBuf, Arc<Vec<Arc<CueSheet>>>>>,
+}
+
+impl CueSheets {
+    /// CUE sheet next to the audio file at `path`, that splits it into several tracks. Sheets
+    /// with a single track per file describe ordinary tracks, so they're ignored
+    pub fn find(&self, path: &Path, listings: &Listings) -> Option<Arc<CueSheet>> {
+        let name = path.file_name()?.to_string_lossy();
+        let sheets = self.dir(path.parent()?, listings);
+
+        sheets
+            .iter()
+            .find(|sheet| {
+                sheet
+                    .tracks_of(&name)
+                    .is_some_and(|tracks| tracks.len() > 1)
+            })
+            .cloned()
+    }
+
+    fn dir(&self, dir: &Path, listings: &Listings) -> Arc<Vec<Arc<CueSheet>>> {
+        if let Some(sheets) = self.lock().get(dir) {
+            return sheets.clone();
+        }
+
+        let listing = listings.get(dir);
+        let sheets = listing
+            .names()
+            .filter(|name| name.ends_with(".cue"))
+            .filter_map(|name| listing.find(OsStr::new(name)))
+            .filter_map(|name| read(&dir.join(name)))
+            .map(Arc::new)
+            .collect();
+        self.lock()
+            .entry(dir.to_owned())
+            .or_insert(Arc::new(sheets))
+            .clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Arc<Vec<Arc<CueSheet>>>>> {
+        self.dirs
+            .lock()
+            .expect("walker thread panicked while holding the lock")
+    }
+}
+
+/// Reads the CUE sheet at `path`. CUE sheets often aren't in UTF-8, so invalid characters are
+/// replaced instead of failing
+fn read(path: &Path) -> Option<CueSheet> {
+    let content = std::fs::read(path)
+        .inspect_err(|e| tracing::warn!(%e, path = %path.display(), "failed to read CUE sheet"))
+        .ok()?;
+
+    CueSheet::parse(&String::from_utf8_lossy(&content))
+        .inspect_err(|e| tracing::warn!(%e, path = %path.display(), "failed to parse CUE sheet"))
+        .ok()
+}
@@ -0,0 +1,590 @@
+use super::file::{self, Caches, NoPathsError, PackError, Sidecar, Tags};
+use super::marker;
+use super::overrides::Override;
+use crate::cli::{Cli, StatsFormat};
+use crate::cue::CueSheet;
+use crate::template::Fields;
+use core::fmt::Write as _;
+use ignore::WalkState;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const UNKNOWN: &str = "(unknown)";
+
+/// Lyrics coverage state of a single track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Synced,
+    Plain,
+    Nolrc,
+    Missing,
+    /// Tags couldn't be read, so lyrics can't be requested for this track
+    Unreadable,
+}
+
+impl State {
+    const ALL: [Self; 5] = [
+        Self::Synced,
+        Self::Plain,
+        Self::Nolrc,
+        Self::Missing,
+        Self::Unreadable,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Synced => "synced",
+            Self::Plain => "plain",
+            Self::Nolrc => "nolrc",
+            Self::Missing => "missing",
+            Self::Unreadable => "unreadable tags",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Track {
+    path: PathBuf,
+    artist: Option<String>,
+    album: Option<String>,
+    state: State,
+}
+
+/// Amount of tracks in each [`State`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Counts([usize; State::ALL.len()]);
+
+impl Counts {
+    pub fn get(&self, state: State) -> usize {
+        self.0[state as usize]
+    }
+
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    /// Share of tracks with any kind of lyrics
+    pub fn coverage(&self) -> f64 {
+        percent(
+            self.get(State::Synced) + self.get(State::Plain),
+            self.total(),
+        )
+    }
+
+    fn add(&mut self, state: State) {
+        self.0[state as usize] += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub total: Counts,
+    pub artists: BTreeMap<String, Counts>,
+    pub albums: BTreeMap<(String, String), Counts>,
+    pub unreadable: Vec<PathBuf>,
+}
+
+impl Report {
+    fn add(&mut self, track: Track) {
+        let artist = track.artist.unwrap_or_else(|| UNKNOWN.to_owned());
+        let album = track.album.unwrap_or_else(|| UNKNOWN.to_owned());
+
+        self.total.add(track.state);
+        self.artists
+            .entry(artist.clone())
+            .or_default()
+            .add(track.state);
+        self.albums
+            .entry((artist, album))
+            .or_default()
+            .add(track.state);
+        if track.state == State::Unreadable {
+            self.unreadable.push(track.path);
+        }
+    }
+}
+
+/// Prints lyrics coverage statistics of all the tracks under `paths` in the given `format`.
+/// Nothing is requested from the network
+#[tracing::instrument(level = "trace", skip(cli))]
+pub async fn stats(paths: Vec<PathBuf>, format: StatsFormat, cli: Cli) -> Result<(), NoPathsError> {
+    let report = tokio::task::spawn_blocking(move || collect(&paths, &cli))
+        .await
+        .expect(super::JOIN_HANDLE_EXPECT_MSG)?;
+
+    let out = match format {
+        StatsFormat::Text => render_text(&report),
+        StatsFormat::Csv => render_csv(&report),
+        StatsFormat::Html => render_html(&report),
+    };
+    print!("{}", out);
+
+    Ok(())
+}
+
+pub fn collect(paths: &[PathBuf], cli: &Cli) -> Result<Report, NoPathsError> {
+    let walk = file::walk_builder(paths, cli)?.build_parallel();
+
+    let report = Mutex::new(Report::default());
+    let caches = Caches::default();
+    walk.run(|| {
+        let report = &report;
+        let caches = &caches;
+        Box::new(move |entry| {
+            match entry {
+                Ok(entry) => {
+                    let tracks = classify(entry.path(), cli, caches);
+                    let mut report = report
+                        .lock()
+                        .expect("walker thread panicked while holding the lock");
+                    tracks.into_iter().for_each(|track| report.add(track));
+                }
+                Err(e) => tracing::warn!(%e),
+            }
+
+            WalkState::Continue
+        })
+    });
+
+    let mut report = report
+        .into_inner()
+        .expect("walker thread panicked while holding the lock");
+    report.unreadable.sort();
+    Ok(report)
+}
+
+/// Classifies tracks at `path` the same way [`file::prepare_entries`] sees them: a file described
+/// by a CUE sheet results in every CUE track, and overrides are applied. Tracks skipped by
+/// overrides aren't counted
+fn classify(path: &Path, cli: &Cli, caches: &Caches) -> Vec<Track> {
+    if !path.is_file() {
+        return Vec::new();
+    }
+    if let Some(sheet) = caches.sheet(path, cli) {
+        return classify_cue(path, &sheet, cli, caches);
+    }
+    let Some(over) = file::overrides_for(path, caches) else {
+        return Vec::new();
+    };
+    let over = readable_override(over);
+
+    let (request, track) = match file::inspect(path, cli) {
+        Ok(None) => return Vec::new(),
+        Ok(Some(tagged_file)) => {
+            let mut tags = file::read_tags(&tagged_file, path, cli);
+            tags.apply_override(&over);
+            let track = tags.track;
+            (file::prepare_lyrics_request(tags), track)
+        }
+        Err(e) => (Err(e), None),
+    };
+
+    let fields = match &request {
+        Ok(request) => Fields {
+            artist: Some(&request.artist),
+            title: Some(&request.title),
+            track,
+        },
+        Err(_) => Fields::default(),
+    };
+    let listings = caches.listings();
+    let state = state(
+        file::find_lrc(path, &fields, cli, listings),
+        file::find_nolrc(path, &fields, cli, listings),
+        over.instrumental == Some(true),
+        request.is_ok(),
+    );
+
+    let (artist, album) = match request {
+        Ok(request) => (Some(request.artist), request.album),
+        Err(PackError::RequestPrepare { artist, .. }) => (artist, None),
+        Err(_) => (None, None),
+    };
+    vec![Track {
+        path: path.to_owned(),
+        artist,
+        album,
+        state,
+    }]
+}
+
+/// Classifies every track of a single-file rip at `path`, described by `sheet`
+fn classify_cue(path: &Path, sheet: &CueSheet, cli: &Cli, caches: &Caches) -> Vec<Track> {
+    let tags = match file::inspect(path, cli) {
+        Ok(None) => return Vec::new(),
+        Ok(Some(tagged_file)) => file::read_tags(&tagged_file, path, cli),
+        Err(_) => Tags::default(),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let listings = caches.listings();
+
+    let mut res = Vec::new();
+    for track in sheet.tracks_of(&name).unwrap_or_default() {
+        let over = readable_override(file::cue_overrides_for(path, track.number, caches));
+        if over.skip() {
+            continue;
+        }
+        let track_tags = file::cue_tags(sheet, track, &over, &tags);
+        let readable = track_tags.artist.is_some() && track_tags.title.is_some();
+
+        let sidecar = Sidecar::for_cue_track(path, track.number, cli);
+        let state = state(
+            listings.find(&sidecar.lrc()),
+            listings.find(&sidecar.nolrc()),
+            over.instrumental == Some(true),
+            readable,
+        );
+        res.push(Track {
+            path: path.to_owned(),
+            artist: track_tags.artist,
+            album: track_tags.album,
+            state,
+        });
+    }
+
+    res
+}
+
+/// Statistics don't fail because of broken override files, the tracks are classified without them
+fn readable_override(over: Result<Override, PackError>) -> Override {
+    over.inspect_err(|e| tracing::warn!(%e, "ignoring overrides"))
+        .unwrap_or_default()
+}
+
+/// State of a track with the existing `lrc` and `nolrc` files. Tracks marked as instrumental by
+/// overrides count as having no lyrics, even before a `.nolrc` file is written for them
+fn state(
+    lrc: Option<PathBuf>,
+    nolrc: Option<PathBuf>,
+    instrumental: bool,
+    readable: bool,
+) -> State {
+    if let Some(lrc) = lrc {
+        match std::fs::read_to_string(&lrc) {
+            // Written by --instrumental-lrc, so there are no lyrics in it
+            Ok(content) if marker::is_instrumental_lrc(&content) => State::Nolrc,
+            Ok(content) if is_synced(&content) => State::Synced,
+            Ok(_) => State::Plain,
+            Err(e) => {
+                tracing::debug!(%e, path = %lrc.display(), "failed to read lyrics file");
+                State::Plain
+            }
+        }
+    } else if nolrc.is_some() || instrumental {
+        State::Nolrc
+    } else if !readable {
+        State::Unreadable
+    } else {
+        State::Missing
+    }
+}
+
+/// Whether lyrics have at least one line with a timestamp, like `[01:23.45]`
+fn is_synced(lyrics: &str) -> bool {
+    lyrics.lines().any(|line| {
+        let Some(rest) = line.trim_start().strip_prefix('[') else {
+            return false;
+        };
+        let Some((minutes, rest)) = rest.split_once(':') else {
+            return false;
+        };
+
+        !minutes.is_empty()
+            && minutes.bytes().all(|b| b.is_ascii_digit())
+            && rest.bytes().next().is_some_and(|b| b.is_ascii_digit())
+    })
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn counts_row(counts: &Counts) -> impl Iterator<Item = String> + '_ {
+    iter_counts(counts).chain([format!("{:.1}%", counts.coverage())])
+}
+
+fn iter_counts(counts: &Counts) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(counts.total())
+        .chain(State::ALL.iter().map(|state| counts.get(*state)))
+        .map(|count| count.to_string())
+}
+
+fn counts_headers() -> impl Iterator<Item = &'static str> {
+    std::iter::once("tracks")
+        .chain(State::ALL.iter().map(|state| state.label()))
+        .chain(["coverage"])
+}
+
+struct Table {
+    title: &'static str,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Overall counts, counts by artist and counts by album
+fn tables(report: &Report) -> [Table; 3] {
+    let total = report.total.total();
+    let states = Table {
+        title: "Overall",
+        headers: vec!["state", "tracks", "share"],
+        rows: State::ALL
+            .iter()
+            .map(|state| (state.label(), report.total.get(*state)))
+            .chain([("total", total)])
+            .map(|(label, count)| {
+                vec![
+                    label.to_owned(),
+                    count.to_string(),
+                    format!("{:.1}%", percent(count, total)),
+                ]
+            })
+            .collect(),
+    };
+
+    let artists = Table {
+        title: "By artist",
+        headers: ["artist"].into_iter().chain(counts_headers()).collect(),
+        rows: report
+            .artists
+            .iter()
+            .map(|(artist, counts)| {
+                std::iter::once(artist.clone())
+                    .chain(counts_row(counts))
+                    .collect()
+            })
+            .collect(),
+    };
+
+    let albums = Table {
+        title: "By album",
+        headers: ["artist", "album"]
+            .into_iter()
+            .chain(counts_headers())
+            .collect(),
+        rows: report
+            .albums
+            .iter()
+            .map(|((artist, album), counts)| {
+                [artist.clone(), album.clone()]
+                    .into_iter()
+                    .chain(counts_row(counts))
+                    .collect()
+            })
+            .collect(),
+    };
+
+    [states, artists, albums]
+}
+
+fn render_text(report: &Report) -> String {
+    let mut out = String::new();
+
+    for (i, table) in tables(report).iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "{}", table.title);
+        text_table(&mut out, &table.headers, &table.rows);
+    }
+
+    if !report.unreadable.is_empty() {
+        out.push_str("\nTracks with unreadable tags\n");
+        for path in &report.unreadable {
+            let _ = writeln!(out, "{}", path.display());
+        }
+    }
+
+    out
+}
+
+/// Writes a table with aligned columns. Columns that only contain numbers are aligned to the
+/// right, the rest are aligned to the left
+fn text_table(out: &mut String, headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let numeric = numeric_columns(headers, rows);
+
+    let mut write_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let line = cells
+            .enumerate()
+            .map(|(i, cell)| {
+                if numeric[i] {
+                    format!("{:>width$}", cell, width = widths[i])
+                } else {
+                    format!("{:<width$}", cell, width = widths[i])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        let _ = writeln!(out, "{}", line.trim_end());
+    };
+
+    write_row(&mut headers.iter().copied());
+    for row in rows {
+        write_row(&mut row.iter().map(String::as_str));
+    }
+}
+
+fn numeric_columns(headers: &[&str], rows: &[Vec<String>]) -> Vec<bool> {
+    (0..headers.len())
+        .map(|i| {
+            rows.iter().all(|row| {
+                row[i]
+                    .trim_end_matches('%')
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b == b'.')
+            })
+        })
+        .collect()
+}
+
+fn render_csv(report: &Report) -> String {
+    let mut out = String::new();
+
+    let headers = ["scope", "artist", "album"]
+        .into_iter()
+        .chain(counts_headers())
+        .collect::<Vec<_>>();
+    csv_row(&mut out, headers);
+
+    let total = ["total", "", ""].map(str::to_owned);
+    csv_row(&mut out, total.into_iter().chain(counts_row(&report.total)));
+    for (artist, counts) in &report.artists {
+        let scope = ["artist".to_owned(), artist.clone(), String::new()];
+        csv_row(&mut out, scope.into_iter().chain(counts_row(counts)));
+    }
+    for ((artist, album), counts) in &report.albums {
+        let scope = ["album".to_owned(), artist.clone(), album.clone()];
+        csv_row(&mut out, scope.into_iter().chain(counts_row(counts)));
+    }
+
+    out
+}
+
+fn csv_row<I, S>(out: &mut String, cells: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let line = cells
+        .into_iter()
+        .map(|cell| {
+            let cell = cell.as_ref();
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    out.push_str(&line);
+    out.push_str("\r\n");
+}
+
+fn render_html(report: &Report) -> String {
+    let mut out = String::new();
+    out.push_str(concat!(
+        "<!DOCTYPE html>\n",
+        "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Lyrics coverage</title>\n",
+        "<style>\n",
+        "body { font-family: sans-serif; margin: 2em; }\n",
+        "table { border-collapse: collapse; margin-bottom: 2em; }\n",
+        "th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; }\n",
+        "th { background: #eee; }\n",
+        "td.num { text-align: right; }\n",
+        "</style>\n</head>\n<body>\n<h1>Lyrics coverage</h1>\n",
+    ));
+
+    for table in tables(report) {
+        let _ = writeln!(out, "<h2>{}</h2>", table.title);
+        html_table(&mut out, &table.headers, &table.rows);
+    }
+
+    if !report.unreadable.is_empty() {
+        out.push_str("<h2>Tracks with unreadable tags</h2>\n<ul>\n");
+        for path in &report.unreadable {
+            let _ = writeln!(out, "<li>{}</li>", escape_html(&path.to_string_lossy()));
+        }
+        out.push_str("</ul>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Writes an HTML table. Columns that only contain numbers are aligned to the right
+fn html_table(out: &mut String, headers: &[&str], rows: &[Vec<String>]) {
+    let numeric = numeric_columns(headers, rows);
+
+    out.push_str("<table>\n<tr>");
+    for header in headers {
+        let _ = write!(out, "<th>{}</th>", escape_html(header));
+    }
+    out.push_str("</tr>\n");
+
+    for row in rows {
+        out.push_str("<tr>");
+        for (i, cell) in row.iter().enumerate() {
+            if numeric[i] {
+                let _ = write!(out, "<td class=\"num\">{}</td>", escape_html(cell));
+            } else {
+                let _ = write!(out, "<td>{}</td>", escape_html(cell));
+            }
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+}
+
+fn escape_html(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_synced() {
+        assert!(is_synced("[ar: artist]\n[00:12.34] line"));
+        assert!(is_synced("[1:02] line"));
+        assert!(!is_synced("[ar: artist]\nplain line"));
+        assert!(!is_synced("just some words: here"));
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        let mut out = String::new();
+        csv_row(&mut out, ["a", "b,c", "d\"e"]);
+        assert_eq!("a,\"b,c\",\"d\"\"e\"\r\n", out);
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!("&lt;b&gt; &amp; &quot;", escape_html("<b> & \""));
+    }
+}
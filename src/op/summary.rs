@@ -0,0 +1,119 @@
+use super::outcome::Outcome;
+use super::output::Track;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Results of a whole run, put together from the reports of every track
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub counts: HashMap<Outcome, usize>,
+    pub failed: Vec<Failure>,
+    /// Errors, that aren't related to a single track, e.g. unreadable directories
+    pub errors: Vec<String>,
+    /// Number of tracks, whose requests got an answer from LRCLIB
+    answered: usize,
+}
+
+/// Track, that failed
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+    pub reason: String,
+}
+
+/// How a run went, as a whole. Tells the exit code of the process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Every track was handled, exits with 0
+    Ok,
+    /// Some tracks failed, exits with 1
+    Failed,
+    /// LRCLIB couldn't be reached at all, exits with 2
+    Fatal,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Ok => Self::SUCCESS,
+            Status::Failed => Self::from(1),
+            Status::Fatal => Self::from(2),
+        }
+    }
+}
+
+impl Summary {
+    pub fn record(&mut self, track: &Track) {
+        *self.counts.entry(track.outcome).or_default() += 1;
+        self.answered += match track.outcome {
+            Outcome::WrittenSynced | Outcome::WrittenPlain | Outcome::NotFound => 1,
+            // Tracks marked as instrumental locally aren't requested, and have no LRCLIB id
+            Outcome::Instrumental => usize::from(track.lrclib_id.is_some()),
+            _ => 0,
+        };
+        if track.outcome.is_failure() {
+            self.failed.push(Failure {
+                path: track.path.clone(),
+                outcome: track.outcome,
+                reason: track
+                    .error
+                    .clone()
+                    .unwrap_or_else(|| track.outcome.label().to_owned()),
+            });
+        }
+    }
+
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.counts.get(&outcome).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// The run is fatal, if requests were sent, but none of them got an answer
+    pub fn status(&self) -> Status {
+        if self.count(Outcome::NetworkError) > 0 && self.answered == 0 {
+            Status::Fatal
+        } else if !self.failed.is_empty() || !self.errors.is_empty() {
+            Status::Failed
+        } else {
+            Status::Ok
+        }
+    }
+}
+
+/// Counts of outcomes on the first line, followed by every failure on its own line
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts = String::new();
+        for outcome in Outcome::ALL {
+            let count = self.count(outcome);
+            if count > 0 {
+                let sep = if counts.is_empty() { ": " } else { ", " };
+                let _ = write!(counts, "{}{} {}", sep, outcome.label(), count);
+            }
+        }
+        let total = self.total();
+        let tracks = if total == 1 { "track" } else { "tracks" };
+        write!(f, "{} {}{}", total, tracks, counts)?;
+
+        if !self.failed.is_empty() {
+            write!(f, "\nfailed tracks:")?;
+            for failure in &self.failed {
+                write!(f, "\n  {}: {}", failure.path.display(), failure.reason)?;
+            }
+        }
+        if !self.errors.is_empty() {
+            write!(f, "\nother errors:")?;
+            for error in &self.errors {
+                write!(f, "\n  {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
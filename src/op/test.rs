@@ -1,7 +1,16 @@
 use super::*;
-use crate::cli::{Cli, FileMatchStrictness, LrcAcquireBehavior};
+use crate::cli::{
+    AudioExts, Cli, Command, FileMatchStrictness, InstrumentalGuess, LrcAcquireBehavior,
+    OutputFormat, TagFilters, WalkFilters,
+};
+use crate::filter::{Filter, TextField};
 use crate::remote::{self, LyricsError, LyricsRequest, LyricsResponse, Remote};
-use std::path::PathBuf;
+use clap::Parser as _;
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile as _, TaggedFileExt as _};
+use lofty::tag::{Accessor as _, ItemKey, Tag, TagType};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tempfile::{env, tempdir_in, NamedTempFile};
 use tokio::fs::try_exists;
@@ -18,6 +27,8 @@ struct TestRemoteImpl<I> {
 
 struct TestRemoteImplInner<I> {
     call_count: usize,
+    /// Artist and title of every request made
+    requests: Vec<(String, String)>,
     iter: I,
 }
 
@@ -25,10 +36,19 @@ impl<I> Remote for TestRemoteImpl<I>
 where
     I: Iterator<Item = remote::Result> + Send,
 {
-    async fn get_lyrics(&self, _req: &LyricsRequest) -> remote::Result {
+    async fn get_lyrics(&self, req: &LyricsRequest) -> remote::Result {
         let mut lock = self.inner.lock().unwrap();
 
         lock.call_count += 1;
+        lock.requests.push((req.artist.clone(), req.title.clone()));
+        lock.iter.next().unwrap()
+    }
+
+    async fn get_lyrics_by_id(&self, id: u64) -> remote::Result {
+        let mut lock = self.inner.lock().unwrap();
+
+        lock.call_count += 1;
+        lock.requests.push(("id".to_owned(), id.to_string()));
         lock.iter.next().unwrap()
     }
 }
@@ -45,6 +65,7 @@ where
             inner: Mutex::new(TestRemoteImplInner {
                 iter: iter.into_iter(),
                 call_count: 0,
+                requests: Vec::new(),
             }),
         }
     }
@@ -63,6 +84,10 @@ impl<I> TestRemoteImpl<I> {
     fn call_count(&self) -> usize {
         self.inner.lock().unwrap().call_count
     }
+
+    fn requests(&self) -> Vec<(String, String)> {
+        self.inner.lock().unwrap().requests.clone()
+    }
 }
 
 fn typical_ok() -> remote::Result {
@@ -78,6 +103,13 @@ fn typical_ok() -> remote::Result {
     })
 }
 
+fn not_found() -> remote::Result {
+    Err(LyricsError::InvalidStatusCode {
+        status: reqwest::StatusCode::NOT_FOUND,
+        url: "url",
+    })
+}
+
 fn typical_err() -> remote::Result {
     Err(LyricsError::InvalidStatusCode {
         status: reqwest::StatusCode::FORBIDDEN,
@@ -85,31 +117,115 @@ fn typical_err() -> remote::Result {
     })
 }
 
+/// Same as [`super::start_up`], but doesn't print the summary, so it doesn't clutter the output
+/// of tests
+async fn start_up_quietly<R>(remote: &'static R, cli: Cli) -> summary::Summary
+where
+    R: Remote + Sync,
+{
+    let output = Arc::new(Output::to(cli.output, std::io::sink()));
+    super::run(remote, cli, output).await
+}
+
 fn typical_cli<I>(paths: I) -> Cli
 where
     I: IntoIterator<Item = PathBuf>,
 {
     Cli {
+        command: None,
         paths: paths.into_iter().collect(),
+        from_stdin: false,
+        null: false,
         no_ignore_hidden: false,
         no_follow_symlinks: false,
+        case_sensitive_ignore: false,
+        git_ignore: false,
         lrc_acquire_behavior: LrcAcquireBehavior::LrcMissing,
         deny_nolrc: false,
+        no_progress: true,
+        output: OutputFormat::Text,
+        retry_not_found: false,
+        instrumental_lrc: false,
+        dry_run: false,
+        offline: false,
+        no_journal: true,
+        journal_dir: None,
+        keep_journals: 20,
+        full_rescan: false,
+        no_index: true,
+        index_file: None,
+        lrc_name: "{stem}{lang}".parse().unwrap(),
+        lang: None,
+        alt_lrc_name: vec![
+            "{filename}.lrc".parse().unwrap(),
+            "{stem}.txt".parse().unwrap(),
+        ],
+        no_alt_lrc_names: false,
+        path_pattern: Vec::new(),
+        artist_separator: [";", " feat. ", " ft. "].map(str::to_owned).to_vec(),
+        rewrite_rules: None,
+        no_cue: false,
         strictness: FileMatchStrictness::FilterByExt,
+        exts: AudioExts::default(),
+        walk: WalkFilters {
+            skip_marker: vec![".nolyrics".to_owned()],
+            ..WalkFilters::default()
+        },
+        tags: TagFilters::default(),
+        instrumental: InstrumentalGuess::default(),
         download_jobs: 1,
         traversal_jobs: 1,
         proxy: None,
+        config: None,
+        profile: None,
     }
 }
 
+/// Creates a valid WAV file with a second of silence, tagged with `artist` and `title`
+fn untagged_track(path: &Path) {
+    const SAMPLE_RATE: u32 = 8000;
+    let data_len = SAMPLE_RATE * 2;
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    std::fs::write(path, wav).expect(CREATE_TEMP_FILE_EXPECT_MSG);
+}
+
+fn tagged_track(path: &Path, artist: &str, title: &str) {
+    let mut tag = Tag::new(TagType::Id3v2);
+    tag.set_artist(artist.to_owned());
+    tag.set_title(title.to_owned());
+    track_with_tag(path, tag);
+}
+
+fn track_with_tag(path: &Path, tag: Tag) {
+    untagged_track(path);
+
+    let mut file = lofty::read_from_path(path).unwrap();
+    file.insert_tag(tag);
+    file.save_to_path(path, WriteOptions::default()).unwrap();
+}
+
 #[tokio::test]
 async fn test_empty_dirs() {
     // 0 files
     let dir = tempdir_in(env::temp_dir()).unwrap();
 
     let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
-    let cli = typical_cli(iter::once(dir.into_path()));
-    super::start_up(remote, cli).await;
+    let cli = typical_cli(iter::once(dir.keep()));
+    start_up_quietly(remote, cli).await;
 
     assert_eq!(0, remote.call_count());
 }
@@ -121,8 +237,8 @@ async fn test_bad_files() {
     let file2 = NamedTempFile::with_suffix_in(".mp3", dir.path()).unwrap();
 
     let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
-    let cli = typical_cli(iter::once(dir.into_path()));
-    super::start_up(remote, cli).await;
+    let cli = typical_cli(iter::once(dir.keep()));
+    start_up_quietly(remote, cli).await;
 
     assert_eq!(0, remote.call_count());
 
@@ -145,10 +261,10 @@ async fn test_bad_files() {
 async fn test_create_nolrc() {
     let file = NamedTempFile::new().expect(CREATE_TEMP_FILE_EXPECT_MSG);
     let mut path = file.path().to_owned();
-    let mut path_clone = path.with_extension("flac");
+    let sidecar = Sidecar::new(&path);
 
-    let res = create_nolrc(&mut path_clone).await;
-    assert!(res.is_ok(), "{:?}", path_clone);
+    let res = create_nolrc(&sidecar, NoLyrics::NotFound, None).await;
+    assert!(res.is_ok(), "{:?}", sidecar);
 
     let og_path_exists = try_exists(&path).await;
     assert!(
@@ -159,25 +275,21 @@ async fn test_create_nolrc() {
     );
 
     path.set_extension("nolrc");
-    assert_eq!(path, path_clone);
+    assert_eq!(path, sidecar.nolrc());
 
     let nolrc_exists = try_exists(&path).await;
     assert!(matches!(nolrc_exists, Ok(true)), "{:?}", path);
 }
 
 #[tokio::test]
-async fn test_replace_nolrc_halfway() {
+async fn test_replace_nolrc_no_nolrc() {
     let lyrics = "some lyrics go here, right?";
     let file = NamedTempFile::new().expect(CREATE_TEMP_FILE_EXPECT_MSG);
     let mut path = file.path().to_owned();
-    let mut path_clone = path.with_extension("flac");
+    let sidecar = Sidecar::new(&path);
 
-    let res = replace_nolrc(&mut path_clone, lyrics).await;
-    assert!(
-        matches!(res, Err(ReplaceNolrcError::Delete(_))),
-        "{:?}",
-        path_clone
-    );
+    let res = replace_nolrc(&sidecar, lyrics, None).await;
+    assert!(res.is_ok(), "{:?}", sidecar);
 
     let og_path_exists = try_exists(&path).await;
     assert!(
@@ -199,18 +311,55 @@ async fn test_replace_nolrc_halfway() {
     assert!(matches!(nolrc_exists, Ok(false)), "{:?}", path);
 }
 
+#[tokio::test]
+async fn test_replace_nolrc_overwrite() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let sidecar = Sidecar::new(dir.path().join("song"));
+    tokio::fs::write(sidecar.lrc(), "old lyrics").await.unwrap();
+
+    let res = replace_nolrc(&sidecar, "new lyrics", None).await;
+    assert!(res.is_ok(), "{:?}", sidecar);
+
+    let lrc_content = tokio::fs::read_to_string(sidecar.lrc()).await;
+    assert_eq!("new lyrics", lrc_content.unwrap());
+    // No temporary files are left behind
+    assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+}
+
+#[tokio::test]
+async fn test_replace_nolrc_rollback() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let sidecar = Sidecar::new(dir.path().join("song"));
+    create_nolrc(&sidecar, NoLyrics::NotFound, None)
+        .await
+        .unwrap();
+    // Renaming a file over a non-empty directory always fails
+    tokio::fs::create_dir(sidecar.lrc()).await.unwrap();
+    tokio::fs::write(sidecar.lrc().join("file"), "")
+        .await
+        .unwrap();
+
+    let res = replace_nolrc(&sidecar, "lyrics", None).await;
+    assert!(matches!(res, Err(ReplaceNolrcError::Write(_))), "{:?}", res);
+
+    let nolrc_exists = try_exists(sidecar.nolrc()).await;
+    assert!(matches!(nolrc_exists, Ok(true)), "{:?}", sidecar);
+    // No temporary files are left behind
+    assert_eq!(2, std::fs::read_dir(dir.path()).unwrap().count());
+}
+
 #[tokio::test]
 async fn test_replace_nolrc_fully() {
     let lyrics = "some lyrics go here, right?";
     let file = NamedTempFile::new().expect(CREATE_TEMP_FILE_EXPECT_MSG);
     let mut path = file.path().to_owned();
-    let mut path_clone = path.with_extension("flac");
+    let sidecar = Sidecar::new(&path);
 
-    let res = create_nolrc(&mut path_clone).await;
-    assert!(res.is_ok(), "{:?}", path_clone);
+    let res = create_nolrc(&sidecar, NoLyrics::NotFound, None).await;
+    assert!(res.is_ok(), "{:?}", sidecar);
 
-    let res = replace_nolrc(&mut path_clone, lyrics).await;
-    assert!(res.is_ok(), "{:?}", path_clone);
+    let res = replace_nolrc(&sidecar, lyrics, None).await;
+    assert!(res.is_ok(), "{:?}", sidecar);
 
     let og_path_exists = try_exists(&path).await;
     assert!(
@@ -231,3 +380,1516 @@ async fn test_replace_nolrc_fully() {
     let nolrc_exists = try_exists(&path).await;
     assert!(matches!(nolrc_exists, Ok(false)), "{:?}", path);
 }
+
+#[tokio::test]
+async fn test_lrc_name() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = dir.path().join("song.wav");
+    tagged_track(&path, "artist", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.lrc_name = "{filename}{lang}".parse().unwrap();
+    cli.lang = Some("en".to_owned());
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(1, remote.call_count());
+    let lrc_content = tokio::fs::read_to_string(dir.path().join("song.wav.en.lrc")).await;
+    assert_eq!("synced_lyrics", lrc_content.unwrap());
+    assert!(matches!(
+        try_exists(dir.path().join("song.lrc")).await,
+        Ok(false)
+    ));
+}
+
+#[tokio::test]
+async fn test_include_exclude_ext() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "artist", "title");
+    tagged_track(&dir.path().join("other.wav"), "artist", "title");
+    std::fs::rename(dir.path().join("other.wav"), dir.path().join("other.dsf")).unwrap();
+    tagged_track(&dir.path().join("skipped.wav"), "artist", "title");
+    std::fs::rename(
+        dir.path().join("skipped.wav"),
+        dir.path().join("skipped.dff"),
+    )
+    .unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.exts.include_ext = vec!["dsf".to_owned()];
+    cli.exts.exclude_ext = vec!["wav".to_owned()];
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(1, remote.call_count());
+    assert!(matches!(
+        try_exists(dir.path().join("other.lrc")).await,
+        Ok(true)
+    ));
+    for name in ["song.lrc", "skipped.lrc"] {
+        assert!(
+            matches!(try_exists(dir.path().join(name)).await, Ok(false)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_path_pattern() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let album = dir.path().join("Artist").join("Album");
+    std::fs::create_dir_all(&album).unwrap();
+    untagged_track(&album.join("01 - Untagged.wav"));
+    tagged_track(
+        &album.join("02 - Wrong.wav"),
+        "Tagged Artist",
+        "Tagged Title",
+    );
+    untagged_track(&dir.path().join("unmatched.wav"));
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.path_pattern = vec!["{artist}/{album}/{track} - {title}".parse().unwrap()];
+    start_up_quietly(remote, cli).await;
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("Artist".to_owned(), "Untagged".to_owned()),
+            ("Tagged Artist".to_owned(), "Tagged Title".to_owned()),
+        ],
+        requests,
+    );
+}
+
+#[tokio::test]
+async fn test_album_artist_fallback() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let mut tag = Tag::new(TagType::Id3v2);
+    tag.insert_text(ItemKey::AlbumArtist, "Album Artist".to_owned());
+    tag.set_title("title".to_owned());
+    track_with_tag(&dir.path().join("song.wav"), tag);
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(
+        vec![("Album Artist".to_owned(), "title".to_owned())],
+        remote.requests(),
+    );
+}
+
+#[tokio::test]
+async fn test_multiple_artists() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "First; Second", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::from_iter([
+        not_found(),
+        not_found(),
+        typical_ok(),
+    ])));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(
+        vec![
+            ("First; Second".to_owned(), "title".to_owned()),
+            ("First".to_owned(), "title".to_owned()),
+            ("Second".to_owned(), "title".to_owned()),
+        ],
+        remote.requests(),
+    );
+    let lrc_content = tokio::fs::read_to_string(dir.path().join("song.lrc")).await;
+    assert_eq!("synced_lyrics", lrc_content.unwrap());
+}
+
+#[tokio::test]
+async fn test_multiple_artists_not_found() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "First & Second", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(not_found)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.artist_separator.push(" & ".to_owned());
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(3, remote.call_count());
+    assert!(matches!(
+        try_exists(dir.path().join("song.nolrc")).await,
+        Ok(true)
+    ));
+
+    // Not split by default
+    tagged_track(&dir.path().join("duo.wav"), "Simon & Garfunkel", "title");
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(4, remote.call_count());
+}
+
+#[tokio::test]
+async fn test_rewrite_rules() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(
+        &dir.path().join("song.wav"),
+        "Beatles, The",
+        "Help! (Remastered)",
+    );
+    let rules = indoc::indoc! {r#"
+        [[rule]]
+        fields = ["artist"]
+        literal = "Beatles, The"
+        replace = "The Beatles"
+
+        [[rule]]
+        fields = ["title"]
+        regex = ' \(Remastered\)$'
+        replace = ""
+    "#};
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.rewrite_rules = Some(rules.parse().unwrap());
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(
+        vec![("The Beatles".to_owned(), "Help!".to_owned())],
+        remote.requests(),
+    );
+}
+
+#[tokio::test]
+async fn test_overrides() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    for name in ["a", "b", "c", "d", "e"] {
+        tagged_track(&path(&format!("{}.wav", name)), "artist", name);
+    }
+    let dir_overrides = indoc::indoc! {r#"
+        artist = "Dir Artist"
+
+        [tracks."b.wav"]
+        title = "Title"
+
+        [tracks.e]
+        instrumental = true
+    "#};
+    std::fs::write(path(".cnova.toml"), dir_overrides).unwrap();
+    std::fs::write(path("c.cnova.toml"), "skip = true").unwrap();
+    std::fs::write(path("d.cnova.toml"), "lrclib_id = 42").unwrap();
+    // Skipped tracks aren't even read
+    std::fs::write(path("broken.wav"), "").unwrap();
+    std::fs::write(path("broken.cnova.toml"), "skip = true").unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    let summary = start_up_quietly(remote, cli).await;
+    assert_eq!(0, summary.count(Outcome::TagError));
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("Dir Artist".to_owned(), "Title".to_owned()),
+            ("Dir Artist".to_owned(), "a".to_owned()),
+            ("id".to_owned(), "42".to_owned()),
+        ],
+        requests,
+    );
+    assert!(matches!(try_exists(path("e.nolrc")).await, Ok(true)));
+    for name in ["c.lrc", "c.nolrc", "e.lrc"] {
+        assert!(
+            matches!(try_exists(path(name)).await, Ok(false)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_overrides() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "artist", "title");
+    std::fs::write(dir.path().join(".cnova.toml"), "skip = tru").unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(0, remote.call_count());
+
+    // A single LRCLIB id for every track in a directory is a mistake
+    std::fs::write(dir.path().join(".cnova.toml"), "lrclib_id = 42").unwrap();
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    let summary = start_up_quietly(remote, cli).await;
+
+    assert_eq!(0, remote.call_count());
+    assert!(
+        summary.errors[0].contains("lrclib_id"),
+        "{:?}",
+        summary.errors
+    );
+}
+
+#[tokio::test]
+async fn test_cue() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    untagged_track(&path("album.wav"));
+    let sheet = indoc::indoc! {r#"
+        PERFORMER "Artist"
+        TITLE "Album"
+        FILE "album.wav" WAVE
+          TRACK 01 AUDIO
+            TITLE "First"
+            INDEX 01 00:00:00
+          TRACK 02 AUDIO
+            TITLE "Second"
+            PERFORMER "Guest"
+            INDEX 01 00:00:40
+          TRACK 03 AUDIO
+            TITLE "Third"
+            INDEX 01 00:00:60
+    "#};
+    std::fs::write(path("album.cue"), sheet).unwrap();
+    std::fs::write(path("album.03.lrc"), "existing").unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("Artist".to_owned(), "First".to_owned()),
+            ("Guest".to_owned(), "Second".to_owned()),
+        ],
+        requests,
+    );
+    for name in ["album.01.lrc", "album.02.lrc"] {
+        let content = tokio::fs::read_to_string(path(name)).await;
+        assert_eq!("synced_lyrics", content.unwrap(), "{}", name);
+    }
+    assert!(matches!(try_exists(path("album.lrc")).await, Ok(false)));
+    assert!(matches!(try_exists(path("album.nolrc")).await, Ok(false)));
+}
+
+#[tokio::test]
+async fn test_cue_overrides() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    untagged_track(&path("album.wav"));
+    let sheet = indoc::indoc! {r#"
+        PERFORMER "Artist"
+        FILE "album.wav" WAVE
+          TRACK 01 AUDIO
+            TITLE "First"
+            INDEX 01 00:00:00
+          TRACK 02 AUDIO
+            TITLE "Second"
+            PERFORMER "Guest"
+            INDEX 01 00:00:40
+          TRACK 03 AUDIO
+            TITLE "Third"
+            INDEX 01 00:00:60
+    "#};
+    std::fs::write(path("album.cue"), sheet).unwrap();
+    let dir_file = indoc::indoc! {r#"
+        [tracks."album.01"]
+        title = "First (Live)"
+
+        [tracks."album.03"]
+        skip = true
+    "#};
+    std::fs::write(path(".cnova.toml"), dir_file).unwrap();
+    std::fs::write(path("album.cnova.toml"), "artist = \"Band\"").unwrap();
+    std::fs::write(path("album.02.cnova.toml"), "instrumental = true").unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(
+        vec![("Band".to_owned(), "First (Live)".to_owned())],
+        remote.requests(),
+    );
+    assert!(matches!(try_exists(path("album.01.lrc")).await, Ok(true)));
+    assert!(matches!(try_exists(path("album.02.nolrc")).await, Ok(true)));
+    assert!(matches!(try_exists(path("album.03.lrc")).await, Ok(false)));
+
+    // Titles can't be set for every track at once
+    std::fs::write(path("album.cnova.toml"), "title = \"Everything\"").unwrap();
+    std::fs::remove_file(path("album.01.lrc")).unwrap();
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    let summary = start_up_quietly(remote, cli).await;
+    assert_eq!(1, remote.call_count());
+    assert!(summary.errors[0].contains("title"), "{:?}", summary.errors);
+}
+
+#[tokio::test]
+async fn test_cue_track_per_file() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    tagged_track(&path("01.wav"), "Artist", "First");
+    tagged_track(&path("02.wav"), "Artist", "Second");
+    let sheet = indoc::indoc! {r#"
+        PERFORMER "Artist"
+        TITLE "Album"
+        FILE "01.wav" WAVE
+          TRACK 01 AUDIO
+            TITLE "First"
+            INDEX 01 00:00:00
+        FILE "02.wav" WAVE
+          TRACK 02 AUDIO
+            TITLE "Second"
+            INDEX 01 00:00:00
+    "#};
+    std::fs::write(path("album.cue"), sheet).unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(2, remote.call_count());
+    for name in ["01.lrc", "02.lrc"] {
+        assert!(matches!(try_exists(path(name)).await, Ok(true)), "{}", name);
+    }
+    for name in ["01.01.lrc", "02.02.lrc"] {
+        assert!(
+            matches!(try_exists(path(name)).await, Ok(false)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_playlist() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let music = dir.path().join("music");
+    let playlists = dir.path().join("playlists");
+    std::fs::create_dir_all(&music).unwrap();
+    std::fs::create_dir_all(&playlists).unwrap();
+    tagged_track(&music.join("listed.wav"), "artist", "listed");
+    tagged_track(&music.join("unlisted.wav"), "artist", "unlisted");
+    let playlist = playlists.join("mix.m3u8");
+    std::fs::write(
+        &playlist,
+        "#EXTM3U\n#EXTINF:1,artist - listed\n../music/listed.wav\n",
+    )
+    .unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(playlist));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(
+        vec![("artist".to_owned(), "listed".to_owned())],
+        remote.requests(),
+    );
+    assert!(matches!(
+        try_exists(music.join("listed.lrc")).await,
+        Ok(true)
+    ));
+}
+
+#[tokio::test]
+async fn test_empty_playlist() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let playlist = dir.path().join("empty.m3u");
+    std::fs::write(&playlist, "#EXTM3U\n").unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(playlist.clone()));
+    let summary = start_up_quietly(remote, cli).await;
+    assert_eq!(summary::Status::Failed, summary.status());
+    assert_eq!(1, summary.errors.len());
+    assert_eq!(0, remote.call_count());
+
+    let cli = typical_cli(iter::empty());
+    assert!(stats::collect(std::slice::from_ref(&playlist), &cli).is_err());
+    assert!(clean::clean(&[playlist], cli).await.is_err());
+}
+
+#[test]
+fn test_from_reader() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    tagged_track(&path("first.wav"), "artist", "first");
+    tagged_track(&path("with\nnewline.wav"), "artist", "second");
+    tagged_track(&path("first.WAV"), "artist", "duplicate");
+
+    let mut input = Vec::new();
+    for name in ["first.wav", "with\nnewline.wav", "first.WAV"] {
+        input.extend_from_slice(path(name).as_os_str().as_encoded_bytes());
+        input.push(b'\0');
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut cli = typical_cli(iter::empty());
+    cli.from_stdin = true;
+    cli.null = true;
+    file::prepare_from_reader(&tx, &cli, io::Cursor::new(input), Caches::default());
+    drop(tx);
+
+    let mut results = Vec::new();
+    while let Ok(res) = rx.try_recv() {
+        results.push(res);
+    }
+    assert_eq!(3, results.len());
+    assert_eq!(path("first.wav"), results[0].as_ref().unwrap().path);
+    assert_eq!(path("with\nnewline.wav"), results[1].as_ref().unwrap().path);
+    assert!(matches!(
+        &results[2],
+        Err(file::PackError::Collision { path: p, .. }) if *p == path("first.WAV")
+    ));
+}
+
+#[tokio::test]
+async fn test_duplicate_paths() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    tagged_track(&path("song.wav"), "artist", "song");
+    let playlist = path("twice.m3u");
+    std::fs::write(&playlist, "song.wav\nsong.wav\n").unwrap();
+
+    // Listed twice in a playlist, and under overlapping paths
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli([playlist, dir.path().to_owned()]);
+    let summary = start_up_quietly(remote, cli).await;
+    assert_eq!(1, remote.call_count());
+    assert!(summary.failed.is_empty(), "{}", summary);
+    assert!(matches!(try_exists(path("song.lrc")).await, Ok(true)));
+
+    // Repeated on stdin
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut input = Vec::new();
+    for _ in 0..2 {
+        input.extend_from_slice(path("song.wav").as_os_str().as_encoded_bytes());
+        input.push(b'\n');
+    }
+    let mut cli = typical_cli(iter::empty());
+    cli.from_stdin = true;
+    cli.lrc_acquire_behavior = LrcAcquireBehavior::All;
+    file::prepare_from_reader(&tx, &cli, io::Cursor::new(input), Caches::default());
+    drop(tx);
+
+    let mut results = Vec::new();
+    while let Ok(res) = rx.try_recv() {
+        results.push(res);
+    }
+    assert_eq!(1, results.len());
+    assert!(results[0].is_ok());
+}
+
+#[test]
+fn test_pending_dirs() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    tagged_track(&path("a.wav"), "artist", "a");
+    tagged_track(&path("b.wav"), "artist", "b");
+    std::fs::write(path("cover.jpg"), "").unwrap();
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    let caches = Caches::default();
+    let listings = listing::Listings::default();
+    let pending = file::Pending::default();
+    let scan = |name: &str| {
+        let packs = file::from_entry(&path(name), &cli, &caches).unwrap();
+        pending.scanned(&tx, &path(name), packs, &cli, &listings);
+    };
+
+    // Packs are held back, until every track of the directory is scanned
+    scan("a.wav");
+    scan("cover.jpg");
+    assert!(rx.try_recv().is_err());
+    scan("b.wav");
+    assert!(rx.try_recv().unwrap().is_ok());
+    assert!(rx.try_recv().unwrap().is_ok());
+    assert!(rx.try_recv().is_err());
+
+    assert!(pending.visit(&path("a.wav")));
+    assert!(!pending.visit(&path("a.wav")));
+}
+
+#[tokio::test]
+async fn test_lrc_name_collision() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "artist", "title");
+    tagged_track(&dir.path().join("song.WAV"), "artist", "title");
+    tagged_track(&dir.path().join("other.wav"), "artist", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(1, remote.call_count());
+    assert!(matches!(
+        try_exists(dir.path().join("song.lrc")).await,
+        Ok(false)
+    ));
+    assert!(matches!(
+        try_exists(dir.path().join("song.nolrc")).await,
+        Ok(false)
+    ));
+    assert!(matches!(
+        try_exists(dir.path().join("other.lrc")).await,
+        Ok(true)
+    ));
+}
+
+#[tokio::test]
+async fn test_existing_lrc_variants() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    for (name, lrc_name) in [
+        ("upper.wav", "Upper.LRC"),
+        ("full.wav", "full.wav.lrc"),
+        ("txt.wav", "txt.txt"),
+        ("nolrc.wav", "NOLRC.NoLrc"),
+    ] {
+        tagged_track(&dir.path().join(name), "artist", "title");
+        std::fs::write(dir.path().join(lrc_name), "").unwrap();
+    }
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::once(dir.path().to_owned()));
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(0, remote.call_count());
+}
+
+#[tokio::test]
+async fn test_no_alt_lrc_names() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "artist", "title");
+    std::fs::write(dir.path().join("song.txt"), "").unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.no_alt_lrc_names = true;
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(1, remote.call_count());
+}
+
+#[tokio::test]
+async fn test_dry_run() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("found.wav"), "artist", "title");
+    tagged_track(&dir.path().join("missing.wav"), "artist", "title");
+    let responses = [
+        typical_ok(),
+        Ok(LyricsResponse {
+            plain_lyrics: None,
+            synced_lyrics: None,
+            ..typical_ok().unwrap()
+        }),
+    ];
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::from_iter(responses)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.dry_run = true;
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(2, remote.call_count());
+    // Nothing but the tracks themselves
+    assert_eq!(2, std::fs::read_dir(dir.path()).unwrap().count());
+}
+
+#[tokio::test]
+async fn test_offline() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "artist", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.dry_run = true;
+    cli.offline = true;
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(0, remote.call_count());
+    assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+}
+
+#[tokio::test]
+async fn test_undo() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let journal_dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("new.wav"), "artist", "title");
+    tagged_track(&dir.path().join("old.wav"), "artist", "title");
+    tagged_track(&dir.path().join("nolrc.wav"), "artist", "title");
+    tokio::fs::write(dir.path().join("old.lrc"), "old lyrics")
+        .await
+        .unwrap();
+    tokio::fs::write(dir.path().join("nolrc.nolrc"), "")
+        .await
+        .unwrap();
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.lrc_acquire_behavior = LrcAcquireBehavior::All;
+    cli.no_journal = false;
+    cli.journal_dir = Some(journal_dir.path().to_owned());
+    start_up_quietly(remote, cli).await;
+
+    assert_eq!(3, remote.call_count());
+    for name in ["new.lrc", "old.lrc", "nolrc.lrc"] {
+        let content = tokio::fs::read_to_string(dir.path().join(name)).await;
+        assert_eq!("synced_lyrics", content.unwrap());
+    }
+    // Neither later runs without any changes nor other directories are undone
+    let later_run = journal_dir.path().join(format!("{}-1", u32::MAX));
+    std::fs::create_dir_all(later_run.join("backups")).unwrap();
+    std::fs::create_dir(journal_dir.path().join("notes")).unwrap();
+
+    let res = journal::undo(journal_dir.path(), None).await;
+    assert!(res.is_ok(), "{:?}", res);
+
+    assert!(matches!(
+        try_exists(dir.path().join("new.lrc")).await,
+        Ok(false)
+    ));
+    assert!(matches!(
+        try_exists(dir.path().join("nolrc.lrc")).await,
+        Ok(false)
+    ));
+    assert!(matches!(
+        try_exists(dir.path().join("nolrc.nolrc")).await,
+        Ok(true)
+    ));
+    let content = tokio::fs::read_to_string(dir.path().join("old.lrc")).await;
+    assert_eq!("old lyrics", content.unwrap());
+    // The journal is gone once it's undone
+    assert_eq!(2, std::fs::read_dir(journal_dir.path()).unwrap().count());
+}
+
+#[tokio::test]
+async fn test_keep_journals() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let journal_dir = tempdir_in(env::temp_dir()).unwrap();
+    tagged_track(&dir.path().join("song.wav"), "artist", "title");
+    for name in ["100-1", "200-1", "300-1", "notes"] {
+        std::fs::create_dir(journal_dir.path().join(name)).unwrap();
+    }
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.no_journal = false;
+    cli.journal_dir = Some(journal_dir.path().to_owned());
+    cli.keep_journals = 2;
+    start_up_quietly(remote, cli).await;
+
+    let mut names: Vec<_> = std::fs::read_dir(journal_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(3, names.len(), "{:?}", names);
+    assert!(names[0].ends_with(&format!("-{}", std::process::id())));
+    assert_eq!("300-1", names[1]);
+    assert_eq!("notes", names[2]);
+}
+
+#[tokio::test]
+async fn test_clean() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let journal_dir = tempdir_in(env::temp_dir()).unwrap();
+    let kept = [
+        "song.flac",
+        "song.lrc",
+        "song.flac.lrc",
+        "song.en.LRC",
+        "other.mp3",
+        "other.nolrc",
+    ];
+    let orphans = [
+        "gone.lrc",
+        "gone.nolrc",
+        "gone.flac.lrc",
+        ".cnova-a1b2c3.tmp",
+        ".cnova-d4e5f6.old",
+    ];
+    for name in kept.iter().chain(&orphans) {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let mut cli = typical_cli(iter::empty());
+    cli.no_journal = false;
+    cli.journal_dir = Some(journal_dir.path().to_owned());
+    let res = clean::clean(&[dir.path().to_owned()], cli).await;
+    assert!(matches!(res, Ok(summary::Status::Ok)));
+
+    for name in kept {
+        assert!(
+            matches!(try_exists(dir.path().join(name)).await, Ok(true)),
+            "{}",
+            name
+        );
+    }
+    for name in orphans {
+        assert!(
+            matches!(try_exists(dir.path().join(name)).await, Ok(false)),
+            "{}",
+            name
+        );
+    }
+
+    let res = journal::undo(journal_dir.path(), None).await;
+    assert!(res.is_ok(), "{:?}", res);
+    for name in orphans {
+        assert!(
+            matches!(try_exists(dir.path().join(name)).await, Ok(true)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_clean_lrc_name() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    tagged_track(&path("01.wav"), "Artist", "Title");
+    let kept = ["Artist - Title.lrc", "artist - title.nolrc", "01.en.lrc"];
+    let orphans = ["Artist - Gone.lrc", "Other - Title.nolrc"];
+    for name in kept.iter().chain(&orphans) {
+        std::fs::write(path(name), "").unwrap();
+    }
+
+    let args = ["cnova", "clean", "--lrc-name", "{artist} - {title}"];
+    let args = args.map(OsString::from).into_iter();
+    let mut cli = Cli::parse_from(args.chain([dir.path().as_os_str().to_owned()]));
+    let Some(Command::Clean { paths }) = cli.command.take() else {
+        panic!("clean wasn't parsed");
+    };
+    let res = clean::clean(&paths, cli).await;
+    assert!(matches!(res, Ok(summary::Status::Ok)));
+
+    for name in kept {
+        assert!(matches!(try_exists(path(name)).await, Ok(true)), "{}", name);
+    }
+    for name in orphans {
+        assert!(
+            matches!(try_exists(path(name)).await, Ok(false)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_clean_excluded_ext() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    untagged_track(&path("excluded.wav"));
+    untagged_track(&path("probed.audio"));
+    std::fs::write(path("notes.txt"), "not a track").unwrap();
+    let kept = ["excluded.lrc", "probed.lrc"];
+    for name in kept.iter().chain(&["notes.lrc"]) {
+        std::fs::write(path(name), "").unwrap();
+    }
+
+    let mut cli = typical_cli(iter::empty());
+    cli.exts.exclude_ext = vec!["wav".to_owned()];
+    let res = clean::clean(&[dir.path().to_owned()], cli).await;
+    assert!(matches!(res, Ok(summary::Status::Ok)));
+
+    for name in kept {
+        assert!(matches!(try_exists(path(name)).await, Ok(true)), "{}", name);
+    }
+    assert!(matches!(try_exists(path("notes.lrc")).await, Ok(false)));
+}
+
+#[tokio::test]
+async fn test_clean_include() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    untagged_track(&path("track.wav"));
+    std::fs::write(path("track.lrc"), "").unwrap();
+    std::fs::write(path("gone.lrc"), "").unwrap();
+
+    // --include picks tracks, so lyrics files are still seen
+    let mut cli = typical_cli(iter::empty());
+    cli.walk.include = vec![globset::Glob::new("*.wav").unwrap()];
+    let res = clean::clean(&[dir.path().to_owned()], cli).await;
+    assert!(matches!(res, Ok(summary::Status::Ok)));
+
+    assert!(matches!(try_exists(path("track.lrc")).await, Ok(true)));
+    assert!(matches!(try_exists(path("gone.lrc")).await, Ok(false)));
+}
+
+#[tokio::test]
+async fn test_clean_cue() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    untagged_track(&path("album.wav"));
+    let sheet = indoc::indoc! {r#"
+        FILE "album.wav" WAVE
+          TRACK 01 AUDIO
+            INDEX 01 00:00:00
+          TRACK 02 AUDIO
+            INDEX 01 00:00:40
+    "#};
+    std::fs::write(path("album.cue"), sheet).unwrap();
+    let kept = ["album.01.en.lrc", "album.02.nolrc"];
+    let orphans = ["album.03.en.lrc", "other.01.en.lrc"];
+    for name in kept.iter().chain(&orphans) {
+        std::fs::write(path(name), "").unwrap();
+    }
+
+    let cli = typical_cli(iter::empty());
+    let res = clean::clean(&[dir.path().to_owned()], cli).await;
+    assert!(matches!(res, Ok(summary::Status::Ok)));
+
+    for name in kept {
+        assert!(matches!(try_exists(path(name)).await, Ok(true)), "{}", name);
+    }
+    for name in orphans {
+        assert!(
+            matches!(try_exists(path(name)).await, Ok(false)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_clean_failed() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    std::fs::write(dir.path().join("gone.lrc"), "").unwrap();
+    let not_a_dir = NamedTempFile::new_in(env::temp_dir()).unwrap();
+
+    // Files, that can't be backed up, aren't deleted
+    let mut cli = typical_cli(iter::empty());
+    cli.no_journal = false;
+    cli.journal_dir = Some(not_a_dir.path().to_owned());
+    let res = clean::clean(&[dir.path().to_owned()], cli).await;
+    assert!(matches!(res, Ok(summary::Status::Failed)));
+    assert!(matches!(
+        try_exists(dir.path().join("gone.lrc")).await,
+        Ok(true)
+    ));
+}
+
+#[tokio::test]
+async fn test_clean_dry_run() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    std::fs::write(dir.path().join("gone.lrc"), "").unwrap();
+
+    let mut cli = typical_cli(iter::empty());
+    cli.dry_run = true;
+    let res = clean::clean(&[dir.path().to_owned()], cli).await;
+    assert!(matches!(res, Ok(summary::Status::Ok)));
+
+    assert!(matches!(
+        try_exists(dir.path().join("gone.lrc")).await,
+        Ok(true)
+    ));
+}
+
+#[test]
+fn test_stats() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    tagged_track(&path("synced.wav"), "Artist", "Synced");
+    tagged_track(&path("plain.wav"), "Artist", "Plain");
+    tagged_track(&path("nolrc.wav"), "Other", "Nolrc");
+    tagged_track(&path("missing.wav"), "Other", "Missing");
+    tagged_track(&path("instrumental.wav"), "Other", "Instrumental");
+    std::fs::write(path("broken.wav"), "").unwrap();
+    std::fs::write(path("synced.lrc"), "[ar: Artist]\n[00:01.00] line").unwrap();
+    std::fs::write(path("plain.lrc"), "line").unwrap();
+    std::fs::write(path("nolrc.nolrc"), "").unwrap();
+    std::fs::write(path("instrumental.lrc"), "[instrumental]\n").unwrap();
+    // Marked by overrides, before any .nolrc file is written
+    tagged_track(&path("marked.wav"), "Other", "Marked");
+    std::fs::write(path("marked.cnova.toml"), "instrumental = true").unwrap();
+    untagged_track(&path("album.wav"));
+    let sheet = indoc::indoc! {r#"
+        PERFORMER "Cue Artist"
+        FILE "album.wav" WAVE
+          TRACK 01 AUDIO
+            TITLE "First"
+            INDEX 01 00:00:00
+          TRACK 02 AUDIO
+            TITLE "Second"
+            INDEX 01 00:00:40
+    "#};
+    std::fs::write(path("album.cue"), sheet).unwrap();
+    std::fs::write(path("album.01.lrc"), "[00:01.00] line").unwrap();
+
+    let cli = typical_cli(iter::empty());
+    let report = stats::collect(&[dir.path().to_owned()], &cli).unwrap();
+
+    for (count, state) in [
+        (2, stats::State::Synced),
+        (1, stats::State::Plain),
+        (3, stats::State::Nolrc),
+        (2, stats::State::Missing),
+        (1, stats::State::Unreadable),
+    ] {
+        assert_eq!(count, report.total.get(state), "{:?}", state);
+    }
+    assert_eq!(vec![path("broken.wav")], report.unreadable);
+
+    let artist = &report.artists["Artist"];
+    assert_eq!(2, artist.total());
+    assert_eq!(100.0, artist.coverage());
+    let other = &report.artists["Other"];
+    assert_eq!(4, other.total());
+    assert_eq!(0.0, other.coverage());
+    let cue_artist = &report.artists["Cue Artist"];
+    assert_eq!(2, cue_artist.total());
+    assert_eq!(50.0, cue_artist.coverage());
+    assert_eq!(1, report.artists["(unknown)"].total());
+}
+
+#[tokio::test]
+async fn test_watch() {
+    let library = tempdir_in(env::temp_dir()).unwrap();
+    let incoming = tempdir_in(env::temp_dir()).unwrap();
+    std::fs::create_dir(incoming.path().join("album")).unwrap();
+    tagged_track(&incoming.path().join("album/moved.wav"), "artist", "moved");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let cli = typical_cli(iter::empty());
+    let paths = vec![library.path().to_owned()];
+    let watch = super::watch::watch(remote, paths, Duration::from_millis(100), cli);
+
+    let arrive = async {
+        // Let the watcher start up first
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        tagged_track(&library.path().join("new.wav"), "artist", "new");
+        std::fs::rename(incoming.path().join("album"), library.path().join("album")).unwrap();
+
+        let lrcs = [
+            library.path().join("new.lrc"),
+            library.path().join("album/moved.lrc"),
+        ];
+        loop {
+            let mut all = true;
+            for lrc in &lrcs {
+                all &= try_exists(lrc).await.unwrap();
+            }
+            if all {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    };
+
+    tokio::select! {
+        res = watch => panic!("stopped watching: {:?}", res),
+        res = tokio::time::timeout(Duration::from_secs(10), arrive) => {
+            res.expect("new tracks weren't picked up")
+        }
+    }
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("artist".to_owned(), "moved".to_owned()),
+            ("artist".to_owned(), "new".to_owned()),
+        ],
+        requests,
+    );
+}
+
+#[tokio::test]
+async fn test_index() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let state = tempdir_in(env::temp_dir()).unwrap();
+    let track = dir.path().join("track.wav");
+    tagged_track(&track, "artist", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(not_found)));
+    let cli = || {
+        let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+        cli.deny_nolrc = true; // the index is the only thing remembering the outcome
+        cli.no_index = false;
+        cli.index_file = Some(state.path().join("index.json"));
+        cli
+    };
+
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(1, remote.call_count());
+    assert!(try_exists(state.path().join("index.json")).await.unwrap());
+
+    // Unchanged, and known to have no lyrics
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(1, remote.call_count());
+
+    let mut full_rescan = cli();
+    full_rescan.full_rescan = true;
+    start_up_quietly(remote, full_rescan).await;
+    assert_eq!(2, remote.call_count());
+
+    // Changed tracks are read again
+    tagged_track(&track, "artist", "new title");
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(3, remote.call_count());
+    assert_eq!(
+        ("artist".to_owned(), "new title".to_owned()),
+        remote.requests()[2],
+    );
+
+    // So are tracks with overrides, and those read with different options
+    std::fs::write(
+        dir.path().join("track.cnova.toml"),
+        "title = \"overridden\"",
+    )
+    .unwrap();
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(4, remote.call_count());
+    assert_eq!(
+        ("artist".to_owned(), "overridden".to_owned()),
+        remote.requests()[3],
+    );
+    std::fs::remove_file(dir.path().join("track.cnova.toml")).unwrap();
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(5, remote.call_count());
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(5, remote.call_count());
+    let mut guessing = cli();
+    guessing.instrumental.min_vocal_duration = Some(0);
+    start_up_quietly(remote, guessing).await;
+    assert_eq!(6, remote.call_count());
+}
+
+#[tokio::test]
+async fn test_index_settings() {
+    let dirs = [(); 2].map(|_| tempdir_in(env::temp_dir()).unwrap());
+    let state = tempdir_in(env::temp_dir()).unwrap();
+    for dir in &dirs {
+        tagged_track(&dir.path().join("track.wav"), "artist", "title");
+    }
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(not_found)));
+    let cli = |dir: &Path, lang: &str| {
+        let mut cli = typical_cli(iter::once(dir.to_owned()));
+        cli.deny_nolrc = true;
+        cli.no_index = false;
+        cli.index_file = Some(state.path().join("index.json"));
+        cli.lang = Some(lang.to_owned());
+        cli
+    };
+
+    start_up_quietly(remote, cli(dirs[0].path(), "en")).await;
+    start_up_quietly(remote, cli(dirs[1].path(), "de")).await;
+    assert_eq!(2, remote.call_count());
+
+    // Entries of one library don't go stale, because another one is scanned with other options
+    start_up_quietly(remote, cli(dirs[0].path(), "en")).await;
+    start_up_quietly(remote, cli(dirs[1].path(), "de")).await;
+    assert_eq!(2, remote.call_count());
+    start_up_quietly(remote, cli(dirs[1].path(), "en")).await;
+    assert_eq!(3, remote.call_count());
+}
+
+#[tokio::test]
+async fn test_index_deleted_nolrc() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let state = tempdir_in(env::temp_dir()).unwrap();
+    let track = dir.path().join("track.wav");
+    tagged_track(&track, "artist", "title");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(not_found)));
+    let cli = || {
+        let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+        cli.no_index = false;
+        cli.index_file = Some(state.path().join("index.json"));
+        cli
+    };
+
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(1, remote.call_count());
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(1, remote.call_count());
+
+    // The index doesn't outweigh the lack of a .nolrc file
+    tokio::fs::remove_file(dir.path().join("track.nolrc"))
+        .await
+        .unwrap();
+    start_up_quietly(remote, cli()).await;
+    assert_eq!(2, remote.call_count());
+}
+
+#[tokio::test]
+async fn test_walk_filters() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    for sub in ["Audiobooks", "Live", "deep", "deep/er"] {
+        std::fs::create_dir(path(sub)).unwrap();
+    }
+    tagged_track(&path("keep.wav"), "artist", "keep");
+    tagged_track(&path("skip me.wav"), "artist", "excluded by name");
+    tagged_track(&path("Live/track.wav"), "artist", "excluded by path");
+    tagged_track(&path("ignored.wav"), "artist", "ignored");
+    std::fs::write(path(".cnovaignore"), "ignored.wav\n").unwrap();
+    tagged_track(&path("Audiobooks/book.wav"), "artist", "marked");
+    std::fs::write(path("Audiobooks/.nolyrics"), "").unwrap();
+    tagged_track(&path("deep/track.wav"), "artist", "deep");
+    tagged_track(&path("deep/er/track.wav"), "artist", "too deep");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.walk.exclude = ["skip *", "**/Live/*.wav"]
+        .map(|glob| {
+            globset::GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .unwrap()
+        })
+        .to_vec();
+    cli.walk.max_depth = Some(2);
+    start_up_quietly(remote, cli).await;
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("artist".to_owned(), "deep".to_owned()),
+            ("artist".to_owned(), "keep".to_owned()),
+        ],
+        requests,
+    );
+}
+
+#[tokio::test]
+async fn test_tag_filters() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let tracks = [
+        ("rock.wav", "Band", "Rock", "1995"),
+        ("old.wav", "Band", "Rock", "1975"),
+        ("classical.wav", "Band", "Classical", "1995"),
+        ("other.wav", "Someone", "Rock", "1995"),
+    ];
+    for (name, artist, genre, year) in tracks {
+        let mut tag = Tag::new(TagType::Id3v2);
+        tag.set_artist(artist.to_owned());
+        tag.set_title(name.to_owned());
+        tag.set_genre(genre.to_owned());
+        tag.insert_text(ItemKey::RecordingDate, year.to_owned());
+        track_with_tag(&path(name), tag);
+    }
+    tagged_track(&path("untagged.wav"), "band", "untagged.wav");
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.tags.filter = vec![r#"genre != "Classical" && (year >= 1990 || !(year > 0))"#
+        .parse()
+        .unwrap()];
+    cli.tags.artist = vec![Filter::glob(TextField::Artist, "b*").unwrap()];
+    start_up_quietly(remote, cli).await;
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("Band".to_owned(), "rock.wav".to_owned()),
+            ("band".to_owned(), "untagged.wav".to_owned()),
+        ],
+        requests,
+    );
+}
+
+#[tokio::test]
+async fn test_guess_instrumental() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    for title in ["Intro", "Introduction", "Song (Off Vocal)", "Forced"] {
+        tagged_track(&path(&format!("{}.wav", title)), "artist", title);
+    }
+    let mut tag = Tag::new(TagType::Id3v2);
+    tag.set_artist("artist".to_owned());
+    tag.set_title("Song".to_owned());
+    tag.set_genre("Instrumental Rock".to_owned());
+    track_with_tag(&path("genre.wav"), tag);
+    // Overrides take precedence over guesses
+    std::fs::write(path("Intro.cnova.toml"), "instrumental = false").unwrap();
+    std::fs::write(path("Forced.cnova.toml"), "instrumental = true").unwrap();
+
+    let guessing_cli = |min_vocal_duration| {
+        let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+        cli.instrumental = InstrumentalGuess {
+            guess_instrumental: true,
+            instrumental_keyword: vec!["intro".to_owned(), "Off Vocal".to_owned()],
+            instrumental_genre: vec!["Instrumental".to_owned()],
+            min_vocal_duration,
+        };
+        cli
+    };
+
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    start_up_quietly(remote, guessing_cli(None)).await;
+
+    let mut requests = remote.requests();
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("artist".to_owned(), "Intro".to_owned()),
+            ("artist".to_owned(), "Introduction".to_owned()),
+        ],
+        requests,
+    );
+    for name in ["Song (Off Vocal).nolrc", "genre.nolrc", "Forced.nolrc"] {
+        assert!(matches!(try_exists(path(name)).await, Ok(true)), "{}", name);
+    }
+
+    // Test tracks are a second long
+    tagged_track(&path("short.wav"), "artist", "short");
+    let remote = Box::leak(Box::new(TestRemoteImpl::with(typical_ok)));
+    start_up_quietly(remote, guessing_cli(Some(2))).await;
+
+    assert_eq!(0, remote.call_count());
+    assert!(matches!(try_exists(path("short.nolrc")).await, Ok(true)));
+}
+
+fn instrumental() -> remote::Result {
+    Ok(LyricsResponse {
+        instrumental: Some(true),
+        plain_lyrics: None,
+        synced_lyrics: None,
+        ..typical_ok().unwrap()
+    })
+}
+
+#[tokio::test]
+async fn test_nolrc_reasons() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let read = |name: &str| std::fs::read_to_string(path(name)).unwrap();
+    let run = |response: fn() -> remote::Result, cli: Cli| async move {
+        let remote = Box::leak(Box::new(TestRemoteImpl::with(response)));
+        start_up_quietly(remote, cli).await;
+        remote.requests()
+    };
+    let cli = || typical_cli(iter::once(dir.path().to_owned()));
+
+    tagged_track(&path("missing.wav"), "artist", "missing");
+    run(not_found, cli()).await;
+    assert_eq!("not-found\n", read("missing.nolrc"));
+
+    tagged_track(&path("instrumental.wav"), "artist", "instrumental");
+    run(instrumental, cli()).await;
+    assert_eq!("instrumental\n", read("instrumental.nolrc"));
+
+    tagged_track(&path("local.wav"), "artist", "local");
+    std::fs::write(path("local.cnova.toml"), "instrumental = true").unwrap();
+    run(typical_ok, cli()).await;
+    assert_eq!("local\n", read("local.nolrc"));
+
+    // Empty markers of older versions are treated as not found
+    tagged_track(&path("old.wav"), "artist", "old");
+    std::fs::write(path("old.nolrc"), "").unwrap();
+
+    // Only tracks, that weren't found, are retried, and their markers are updated
+    let mut retry = cli();
+    retry.retry_not_found = true;
+    let mut requests = run(instrumental, retry).await;
+    requests.sort();
+    assert_eq!(
+        vec![
+            ("artist".to_owned(), "missing".to_owned()),
+            ("artist".to_owned(), "old".to_owned()),
+        ],
+        requests,
+    );
+    assert_eq!("instrumental\n", read("missing.nolrc"));
+    assert_eq!("instrumental\n", read("old.nolrc"));
+    assert_eq!("local\n", read("local.nolrc"));
+
+    // Local markers are retried too, once overrides or guessing don't apply anymore
+    std::fs::remove_file(path("local.cnova.toml")).unwrap();
+    let mut retry = cli();
+    retry.retry_not_found = true;
+    let requests = run(not_found, retry).await;
+    assert_eq!(vec![("artist".to_owned(), "local".to_owned())], requests);
+    assert_eq!("not-found\n", read("local.nolrc"));
+
+    tagged_track(&path("lrc.wav"), "artist", "lrc");
+    let mut instrumental_lrc = cli();
+    instrumental_lrc.instrumental_lrc = true;
+    run(instrumental, instrumental_lrc).await;
+    assert_eq!("[instrumental]\n", read("lrc.lrc"));
+    assert!(matches!(try_exists(path("lrc.nolrc")).await, Ok(false)));
+
+    // Guesses don't get an .lrc file, so they can be retried
+    tagged_track(&path("guessed.wav"), "artist", "Intro");
+    let mut guessing = cli();
+    guessing.instrumental_lrc = true;
+    guessing.instrumental.guess_instrumental = true;
+    guessing.instrumental.instrumental_keyword = vec!["intro".to_owned()];
+    run(instrumental, guessing).await;
+    assert_eq!("local\n", read("guessed.nolrc"));
+    assert!(matches!(try_exists(path("guessed.lrc")).await, Ok(false)));
+}
+
+#[tokio::test]
+async fn test_progress_status() {
+    let progress = Arc::new(progress::Progress::default());
+    progress.scanned();
+    progress.scanned();
+    progress.queued();
+    progress.queued();
+    let in_flight = progress.in_flight();
+    progress.done(Outcome::WrittenSynced);
+    assert!(
+        progress
+            .status()
+            .starts_with("2 files scanned, 1/2 tracks done, 1 requests in flight (synced 1), "),
+        "{}",
+        progress.status(),
+    );
+    assert!(!progress.status().contains("ETA"));
+
+    drop(in_flight);
+    progress.finish_scan();
+    assert!(progress.status().contains(", 0 requests in flight"));
+    assert!(progress.status().contains("ETA"));
+}
+
+/// Writer, whose output can be read, after it's moved into an [`Output`]
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut *self.0.lock().unwrap(), buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuf {
+    fn lines(&self) -> Vec<serde_json::Value> {
+        let buf = self.0.lock().unwrap();
+        std::str::from_utf8(&buf)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn test_json_output() {
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let run = |response: fn() -> remote::Result, paths: Vec<PathBuf>| async move {
+        let remote = Box::leak(Box::new(TestRemoteImpl::with(response)));
+        let buf = SharedBuf::default();
+        let output = Arc::new(Output::to(OutputFormat::Json, buf.clone()));
+        let mut cli = typical_cli(paths);
+        cli.output = OutputFormat::Json;
+        super::run(remote, cli, output).await;
+
+        let mut lines = buf.lines();
+        let summary = lines.pop().unwrap();
+        lines.sort_by_key(|line| line["path"].as_str().unwrap().to_owned());
+        (lines, summary)
+    };
+
+    tagged_track(&path("a.wav"), "artist", "title");
+    tagged_track(&path("b.wav"), "artist", "existing");
+    std::fs::write(path("b.lrc"), "lyrics").unwrap();
+    untagged_track(&path("c.wav"));
+
+    let (tracks, summary) = run(typical_ok, vec![dir.path().to_owned()]).await;
+    let [synced, skipped, tag_error] = &tracks[..] else {
+        panic!("expected 3 tracks, got {:?}", tracks);
+    };
+
+    assert_eq!("track", synced["type"]);
+    assert_eq!("written-synced", synced["outcome"]);
+    assert_eq!("artist", synced["request"]["artist"]);
+    assert_eq!("title", synced["request"]["title"]);
+    assert_eq!(path("a.lrc").to_str().unwrap(), synced["lyrics"]);
+    assert_eq!(0, synced["lrclib_id"]);
+    assert!(synced.get("error").is_none());
+
+    assert_eq!("skipped-existing", skipped["outcome"]);
+    assert_eq!(path("b.lrc").to_str().unwrap(), skipped["lyrics"]);
+
+    assert_eq!("tag-error", tag_error["outcome"]);
+    assert!(tag_error["request"].is_null());
+    assert!(tag_error["error"].is_string());
+
+    assert_eq!("summary", summary["type"]);
+    assert_eq!(3, summary["total"]);
+    assert_eq!(1, summary["outcomes"]["written-synced"]);
+    assert_eq!(1, summary["outcomes"]["skipped-existing"]);
+    assert_eq!(1, summary["outcomes"]["tag-error"]);
+    assert_eq!(0, summary["outcomes"]["not-found"]);
+    assert_eq!(1, summary["failed"].as_array().unwrap().len());
+    assert_eq!(tag_error["path"], summary["failed"][0]["path"]);
+    assert_eq!("tag-error", summary["failed"][0]["outcome"]);
+
+    tagged_track(&path("d.wav"), "artist", "failing");
+    let (tracks, summary) = run(typical_err, vec![path("d.wav")]).await;
+    assert_eq!(1, tracks.len());
+    assert_eq!("network-error", tracks[0]["outcome"]);
+    assert!(tracks[0]["lyrics"].is_null());
+    assert!(tracks[0]["error"].is_string());
+    assert_eq!(1, summary["outcomes"]["network-error"]);
+}
+
+#[tokio::test]
+async fn test_summary() {
+    use summary::Status;
+
+    let dir = tempdir_in(env::temp_dir()).unwrap();
+    let path = |name: &str| dir.path().join(name);
+    let run = |response: fn() -> remote::Result, paths: Vec<PathBuf>| async move {
+        let remote = Box::leak(Box::new(TestRemoteImpl::with(response)));
+        let mut cli = typical_cli(paths);
+        cli.lrc_acquire_behavior = LrcAcquireBehavior::All;
+        start_up_quietly(remote, cli).await
+    };
+
+    tagged_track(&path("a.wav"), "artist", "a");
+    let summary = run(typical_ok, vec![dir.path().to_owned()]).await;
+    assert_eq!(Status::Ok, summary.status());
+    assert_eq!(1, summary.count(Outcome::WrittenSynced));
+    assert_eq!("1 track: synced 1", summary.to_string());
+
+    // Every request failing means LRCLIB can't be reached
+    let summary = run(typical_err, vec![dir.path().to_owned()]).await;
+    assert_eq!(Status::Fatal, summary.status());
+    assert_eq!(path("a.wav"), summary.failed[0].path);
+    assert_eq!(Outcome::NetworkError, summary.failed[0].outcome);
+
+    // Tracks, that fail by themselves, don't make the run fatal
+    untagged_track(&path("b.wav"));
+    let summary = run(typical_ok, vec![dir.path().to_owned()]).await;
+    assert_eq!(Status::Failed, summary.status());
+    assert_eq!(1, summary.failed.len());
+    assert_eq!(path("b.wav"), summary.failed[0].path);
+    assert_eq!(Outcome::TagError, summary.failed[0].outcome);
+    let text = summary.to_string();
+    assert!(
+        text.starts_with("2 tracks: synced 1, tag errors 1\nfailed tracks:\n  "),
+        "{}",
+        text,
+    );
+
+    // Instrumental tracks got an answer too
+    tagged_track(&path("b.wav"), "artist", "b");
+    let responses = [instrumental(), typical_err()];
+    let remote = Box::leak(Box::new(TestRemoteImpl::from_iter(responses)));
+    let mut cli = typical_cli(iter::once(dir.path().to_owned()));
+    cli.lrc_acquire_behavior = LrcAcquireBehavior::All;
+    let summary = start_up_quietly(remote, cli).await;
+    assert_eq!(1, summary.count(Outcome::Instrumental));
+    assert_eq!(Status::Failed, summary.status());
+}
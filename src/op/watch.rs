@@ -0,0 +1,244 @@
+use super::file::{self, Caches, PacksTx};
+use super::prune::Prune;
+use super::{handle_all, open_journal, report_journal, EntryOpts, Records, JOIN_HANDLE_EXPECT_MSG};
+use crate::cli::Cli;
+use crate::remote::Remote;
+use notify::event::{AccessKind, AccessMode, EventKind, ModifyKind, RenameMode};
+use notify::{Event, RecursiveMode, Watcher as _};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often pending files are checked, at most
+const TICK: Duration = Duration::from_millis(250);
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error("failed to set up file watcher: {0}")]
+    Init(#[source] notify::Error),
+    #[error("failed to watch {}: {source}", path.display())]
+    Watch {
+        path: PathBuf,
+        #[source]
+        source: notify::Error,
+    },
+}
+
+/// Watches `paths` for new tracks, and handles them the same way [`super::start_up`] does, once
+/// they stay unchanged for `settle`. Runs until the watcher fails
+#[tracing::instrument(level = "trace", skip(remote), parent = None)]
+pub async fn watch<R>(
+    remote: &'static R,
+    paths: Vec<PathBuf>,
+    settle: Duration,
+    cli: Cli,
+) -> Result<(), WatchError>
+where
+    R: Remote + Sync,
+{
+    let opts = EntryOpts::from(&cli);
+    let journal = open_journal(&cli);
+    let records = Records {
+        journal: journal.clone(),
+        ..Records::default()
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(cli.download_jobs.into()));
+
+    // Neither of these finishes on its own: the watcher keeps the channel open, and stops only
+    // once it fails, or nothing receives from the channel anymore
+    let watcher = tokio::task::spawn_blocking(move || watch_entries(&tx, &paths, settle, &cli));
+    let ((), res) = tokio::join!(
+        handle_all(remote, semaphore, &mut rx, opts, records),
+        watcher,
+    );
+
+    report_journal(journal.as_deref()).await;
+    res.expect(JOIN_HANDLE_EXPECT_MSG)
+}
+
+fn watch_entries(
+    tx: &PacksTx,
+    paths: &[PathBuf],
+    settle: Duration,
+    cli: &Cli,
+) -> Result<(), WatchError> {
+    let (events_tx, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(events_tx).map_err(WatchError::Init)?;
+    for path in paths {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(|source| WatchError::Watch {
+                path: path.clone(),
+                source,
+            })?;
+    }
+    tracing::info!(?paths, "watching for new tracks");
+
+    let mut pending = Pending::new(paths, cli);
+    while !tx.is_closed() {
+        match events.recv_timeout(TICK.min(settle)) {
+            Ok(Ok(event)) => pending.note(event, cli),
+            Ok(Err(e)) => tracing::warn!(%e, "file watcher reported an error"),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let settled = pending.settled(settle);
+        if settled.is_empty() {
+            continue;
+        }
+
+        // Lyrics files and overrides might've changed since the last batch, so nothing is reused
+        let caches = Caches::default();
+        let mut targets = HashMap::new();
+        for path in settled {
+            tracing::debug!(path = %path.display(), "file settled");
+            match file::from_entry(&path, cli, &caches) {
+                Ok(packs) => packs
+                    .into_iter()
+                    .for_each(|pack| file::send_first(tx, pack, &mut targets)),
+                Err(e) => file::send(tx, Err(e)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Files that were created or changed recently, and might still be written to
+#[derive(Debug)]
+struct Pending {
+    files: HashMap<PathBuf, Seen>,
+    /// Watched directories
+    roots: Vec<PathBuf>,
+    prune: Prune,
+}
+
+#[derive(Debug)]
+struct Seen {
+    /// Size and modification time, the last time the file was looked at
+    meta: Option<(u64, SystemTime)>,
+    since: Instant,
+}
+
+impl Pending {
+    fn new(roots: &[PathBuf], cli: &Cli) -> Self {
+        Self {
+            files: HashMap::new(),
+            roots: roots.to_vec(),
+            prune: Prune::new(&cli.walk),
+        }
+    }
+
+    /// Remembers the files affected by `event`. Directories, that were moved in, are traversed,
+    /// since there are no events for the files inside them
+    fn note(&mut self, event: Event, cli: &Cli) {
+        let relevant = matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(
+                    ModifyKind::Any
+                        | ModifyKind::Data(_)
+                        | ModifyKind::Name(RenameMode::To | RenameMode::Both | RenameMode::Any)
+                )
+                | EventKind::Access(AccessKind::Close(AccessMode::Write))
+        );
+        if !relevant {
+            return;
+        }
+
+        for path in event.paths {
+            let is_dir = path.is_dir();
+            if !self.keeps(&path, is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                self.note_dir(&path, cli);
+            } else if is_candidate(&path, cli) {
+                self.touch(path);
+            }
+        }
+    }
+
+    /// Whether `path` passes the same filters, it would have to pass when walking
+    fn keeps(&self, path: &Path, is_dir: bool) -> bool {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .any(|root| self.prune.keeps_nested(path, is_dir, root))
+    }
+
+    fn note_dir(&mut self, dir: &Path, cli: &Cli) {
+        let Ok(walk) = file::walk_builder(&[dir.to_owned()], cli) else {
+            return;
+        };
+
+        for entry in walk.build() {
+            match entry {
+                Ok(entry) if is_candidate(entry.path(), cli) => self.touch(entry.into_path()),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(%e, "failed to traverse new directory"),
+            }
+        }
+    }
+
+    fn touch(&mut self, path: PathBuf) {
+        let meta = meta(&path);
+        self.files.insert(
+            path,
+            Seen {
+                meta,
+                since: Instant::now(),
+            },
+        );
+    }
+
+    /// Takes out the files, that haven't changed for `settle`. Files that are gone are forgotten
+    fn settled(&mut self, settle: Duration) -> Vec<PathBuf> {
+        let mut res = Vec::new();
+
+        self.files.retain(|path, seen| {
+            if seen.since.elapsed() < settle {
+                return true;
+            }
+
+            match meta(path) {
+                None => false,
+                Some(meta) if seen.meta == Some(meta) => {
+                    res.push(path.clone());
+                    false
+                }
+                meta => {
+                    *seen = Seen {
+                        meta,
+                        since: Instant::now(),
+                    };
+                    true
+                }
+            }
+        });
+
+        res.sort();
+        res
+    }
+}
+
+/// Whether `path` could be a track. Lyrics files, partial downloads and other temporary files
+/// are rejected right away, so they don't end up in the logs
+fn is_candidate(path: &Path, cli: &Cli) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    let audio = path.extension().is_some_and(|ext| cli.exts.contains(ext));
+
+    audio && (cli.no_ignore_hidden || !hidden)
+}
+
+fn meta(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = std::fs::metadata(path).ok().filter(|meta| meta.is_file())?;
+    Some((meta.len(), meta.modified().ok()?))
+}
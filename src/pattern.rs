@@ -0,0 +1,167 @@
+use regex::Regex;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Pattern of track paths, used to derive metadata from file and directory names, when tags are
+/// missing. The pattern is matched against the end of the path, without the extension. `/`
+/// separates path components
+///
+/// Supported placeholders are:
+/// - `{artist}`, `{album}`, `{title}`: any text within a single path component
+/// - `{track}`: a track number
+/// - `{_}`: any text within a single path component, that is ignored
+///
+/// For example, `{artist}/{album}/{track} - {title}` matches `Artist/Album/01 - Title.mp3`
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+    source: String,
+    regex: Regex,
+}
+
+/// Values captured from a path by a [`PathPattern`]
+#[derive(Debug, Default, PartialEq)]
+pub struct PathFields {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub track: Option<u32>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum PatternError {
+    #[error("pattern is empty")]
+    Empty,
+    #[error("unknown placeholder \"{{{0}}}\"")]
+    UnknownPlaceholder(String),
+    #[error("placeholder \"{{{0}}}\" is used more than once")]
+    Duplicate(String),
+    #[error("unclosed placeholder")]
+    Unclosed,
+}
+
+impl FromStr for PathPattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(PatternError::Empty);
+        }
+
+        let mut regex = String::from("(?:^|/)");
+        let mut seen = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            regex.push_str(&regex::escape(&rest[..start]));
+
+            let end = rest[start..].find('}').ok_or(PatternError::Unclosed)? + start;
+            let name = &rest[start + 1..end];
+            let group = match name {
+                "artist" | "album" | "title" => format!("(?P<{}>[^/]+?)", name),
+                "track" => "(?P<track>[0-9]+)".to_owned(),
+                "_" => "[^/]*?".to_owned(),
+                other => return Err(PatternError::UnknownPlaceholder(other.to_owned())),
+            };
+            if name != "_" {
+                if seen.contains(&name) {
+                    return Err(PatternError::Duplicate(name.to_owned()));
+                }
+                seen.push(name);
+            }
+            regex.push_str(&group);
+            rest = &rest[end + 1..];
+        }
+        regex.push_str(&regex::escape(rest));
+        regex.push('$');
+
+        Ok(Self {
+            source: s.to_owned(),
+            regex: Regex::new(&regex).expect("escaped pattern has to be a valid regex"),
+        })
+    }
+}
+
+impl PathPattern {
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Matches this pattern against `path`, returning the captured values, if it matched.
+    /// Captured values are trimmed
+    pub fn captures(&self, path: &Path) -> Option<PathFields> {
+        let path = path.with_extension("");
+        let path = path.to_string_lossy();
+        let path = if cfg!(windows) {
+            path.replace('\\', "/")
+        } else {
+            path.into_owned()
+        };
+
+        let captures = self.regex.captures(&path)?;
+        let text = |name| {
+            captures
+                .name(name)
+                .map(|m| m.as_str().trim().to_owned())
+                .filter(|s| !s.is_empty())
+        };
+
+        Some(PathFields {
+            artist: text("artist"),
+            album: text("album"),
+            title: text("title"),
+            track: captures.name("track").and_then(|m| m.as_str().parse().ok()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert!("{artist}/{album}/{track} - {title}"
+            .parse::<PathPattern>()
+            .is_ok());
+        assert_eq!(
+            Err(PatternError::Empty),
+            "".parse::<PathPattern>().map(|_| ())
+        );
+        assert_eq!(
+            Err(PatternError::UnknownPlaceholder("genre".to_owned())),
+            "{genre}".parse::<PathPattern>().map(|_| ()),
+        );
+        assert_eq!(
+            Err(PatternError::Duplicate("title".to_owned())),
+            "{title} - {title}".parse::<PathPattern>().map(|_| ()),
+        );
+        assert_eq!(
+            Err(PatternError::Unclosed),
+            "{title".parse::<PathPattern>().map(|_| ()),
+        );
+    }
+
+    #[test]
+    fn test_captures() {
+        let pattern: PathPattern = "{artist}/{album}/{track} - {title}".parse().unwrap();
+        let fields = pattern.captures(Path::new("/music/Artist/Some Album/01 - A Title.mp3"));
+        assert_eq!(
+            Some(PathFields {
+                artist: Some("Artist".to_owned()),
+                album: Some("Some Album".to_owned()),
+                title: Some("A Title".to_owned()),
+                track: Some(1),
+            }),
+            fields,
+        );
+
+        assert_eq!(None, pattern.captures(Path::new("Album/A Title.mp3")));
+    }
+
+    #[test]
+    fn test_captures_ignored() {
+        let pattern: PathPattern = "{artist} - {_}/{title}".parse().unwrap();
+        let fields = pattern.captures(Path::new("Artist - 1999 - Album/Title.flac"));
+        assert_eq!(Some("Artist".to_owned()), fields.as_ref().unwrap().artist);
+        assert_eq!(Some("Title".to_owned()), fields.unwrap().title);
+    }
+}
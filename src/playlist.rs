@@ -0,0 +1,122 @@
+use std::path::{Path, PathBuf};
+
+/// Whether `path` looks like an M3U or M3U8 playlist
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"))
+}
+
+/// Replaces playlists in `paths` with the tracks they reference. Other paths are kept as is.
+/// Playlists, that can't be read, are kept as well, they're skipped later on as non-audio files
+pub fn expand(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut res = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        if !is_playlist(path) || !path.is_file() {
+            res.push(path.clone());
+            continue;
+        }
+
+        match std::fs::read(path) {
+            Ok(content) => {
+                let base = path.parent().unwrap_or(Path::new(""));
+                let tracks = parse(&String::from_utf8_lossy(&content), base);
+                tracing::debug!(path = %path.display(), count = tracks.len(), "expanded playlist");
+                res.extend(tracks);
+            }
+            Err(e) => {
+                tracing::warn!(%e, path = %path.display(), "failed to read playlist");
+                res.push(path.clone());
+            }
+        }
+    }
+
+    res
+}
+
+/// Paths of the tracks referenced by an M3U playlist. Relative paths are resolved against
+/// `base`, the directory of the playlist. Comments, `#EXT` directives and URLs, except for
+/// `file://` ones, are skipped
+fn parse(content: &str, base: &Path) -> Vec<PathBuf> {
+    content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line.strip_prefix("file://") {
+            Some(path) => Some(percent_decode(path)),
+            None if line.contains("://") => None,
+            None => Some(line.to_owned()),
+        })
+        .map(|line| {
+            // Playlists made on Windows use backslashes, which aren't separators anywhere else
+            if cfg!(not(windows)) && !line.contains('/') {
+                line.replace('\\', "/")
+            } else {
+                line
+            }
+        })
+        .map(|line| base.join(line))
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                res.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                res.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let content = indoc::indoc! {"
+            #EXTM3U
+            #EXTINF:123,Artist - Title
+            Artist/Album/01 Title.flac
+
+            /music/absolute.mp3
+            https://example.com/stream.mp3
+            file:///music/with%20space.mp3
+        "};
+
+        assert_eq!(
+            vec![
+                PathBuf::from("/playlists/Artist/Album/01 Title.flac"),
+                PathBuf::from("/music/absolute.mp3"),
+                PathBuf::from("/music/with space.mp3"),
+            ],
+            parse(content, Path::new("/playlists")),
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_parse_backslashes() {
+        assert_eq!(
+            vec![PathBuf::from("base/Artist/Title.mp3")],
+            parse("Artist\\Title.mp3", Path::new("base")),
+        );
+    }
+}
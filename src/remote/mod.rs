@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::future::Future;
 use std::time::Duration;
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LyricsRequest {
     #[serde(rename = "artist_name")]
     pub artist: String,
@@ -71,4 +71,7 @@ pub type Result = std::result::Result<LyricsResponse, LyricsError>;
 
 pub trait Remote {
     fn get_lyrics(&self, req: &LyricsRequest) -> impl Future<Output = Result> + Send;
+
+    /// Fetches lyrics by their LRCLIB id, instead of searching by tags
+    fn get_lyrics_by_id(&self, id: u64) -> impl Future<Output = Result> + Send;
 }
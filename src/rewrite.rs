@@ -0,0 +1,259 @@
+use core::fmt;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization as _;
+
+/// Rules, that rewrite tag values before they're sent to LRCLIB. Loaded from a TOML file, where
+/// every `[[rule]]` table is a single rule. Rules are applied in order, each one to the result of
+/// the previous one
+///
+/// ```toml
+/// [[rule]]
+/// normalize = "nfkc"
+///
+/// [[rule]]
+/// fields = ["artist"]
+/// literal = "Beatles, The"
+/// replace = "The Beatles"
+///
+/// [[rule]]
+/// fields = ["title"]
+/// regex = '\s*\(Remastered( \d{4})?\)$'
+/// replace = ""
+/// ```
+///
+/// - `literal` replaces the whole value, if it's equal to the given one
+/// - `regex` replaces all matches of the given expression. `replace` may refer to capture groups,
+///   like `$1`
+/// - `normalize` converts the value to the given Unicode normalization form, `nfc` or `nfkc`
+///
+/// Rules apply to the artist, title and album, unless `fields` are specified
+#[derive(Clone, Debug, Default)]
+pub struct Rewrites {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Artist,
+    Title,
+    Album,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Artist => "artist",
+            Self::Title => "title",
+            Self::Album => "album",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Form {
+    Nfc,
+    Nfkc,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    /// Fields this rule applies to. Empty means all of them
+    fields: Vec<Field>,
+    kind: Kind,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Literal { from: String, to: String },
+    Regex { regex: Regex, replace: String },
+    Normalize(Form),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRewrites {
+    #[serde(default, rename = "rule")]
+    rules: Vec<RawRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    #[serde(default)]
+    fields: Vec<Field>,
+    literal: Option<String>,
+    regex: Option<String>,
+    replace: Option<String>,
+    normalize: Option<Form>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RewriteError {
+    #[error("failed to read rewrite rules: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("failed to parse rewrite rules: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("rule #{index}: {reason}")]
+    Rule { index: usize, reason: String },
+}
+
+impl FromStr for Rewrites {
+    type Err = RewriteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw: RawRewrites = toml::from_str(s)?;
+
+        let rules = raw
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(i, raw)| {
+                Rule::try_from(raw).map_err(|reason| RewriteError::Rule {
+                    index: i + 1,
+                    reason,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { rules })
+    }
+}
+
+impl TryFrom<RawRule> for Rule {
+    type Error = String;
+
+    fn try_from(raw: RawRule) -> Result<Self, Self::Error> {
+        let kind = match (raw.literal, raw.regex, raw.normalize, raw.replace) {
+            (Some(from), None, None, Some(to)) => Kind::Literal { from, to },
+            (None, Some(regex), None, Some(replace)) => Kind::Regex {
+                regex: Regex::new(&regex).map_err(|e| e.to_string())?,
+                replace,
+            },
+            (None, None, Some(form), None) => Kind::Normalize(form),
+            (None, None, Some(_), Some(_)) => {
+                return Err("\"normalize\" doesn't take \"replace\"".to_owned())
+            }
+            (Some(_), None, None, None) | (None, Some(_), None, None) => {
+                return Err("\"replace\" is missing".to_owned())
+            }
+            _ => {
+                return Err(
+                    "rule has to have exactly one of \"literal\", \"regex\" or \"normalize\""
+                        .to_owned(),
+                )
+            }
+        };
+
+        Ok(Self {
+            fields: raw.fields,
+            kind,
+        })
+    }
+}
+
+impl Rewrites {
+    /// Loads rules from the TOML file at `path`
+    pub fn load(path: &Path) -> Result<Self, RewriteError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Applies all the rules for `field` to `value`. Returns [`None`], if nothing changed
+    pub fn apply(&self, field: Field, value: &str) -> Option<String> {
+        let mut res = value.to_owned();
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !rule.fields.is_empty() && !rule.fields.contains(&field) {
+                continue;
+            }
+
+            let new = match &rule.kind {
+                Kind::Literal { from, to } if res == *from => to.clone(),
+                Kind::Literal { .. } => continue,
+                Kind::Regex { regex, replace } => {
+                    regex.replace_all(&res, replace.as_str()).into_owned()
+                }
+                Kind::Normalize(Form::Nfc) => res.nfc().collect(),
+                Kind::Normalize(Form::Nfkc) => res.nfkc().collect(),
+            };
+            if new != res {
+                tracing::debug!(%field, from = res, to = new, rule = i + 1, "rule applied");
+                res = new;
+            }
+        }
+
+        (res != value).then_some(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RULES: &str = r#"
+        [[rule]]
+        normalize = "nfkc"
+
+        [[rule]]
+        fields = ["artist"]
+        literal = "Beatles, The"
+        replace = "The Beatles"
+
+        [[rule]]
+        fields = ["title", "album"]
+        regex = '\s*\(Remastered( \d{4})?\)$'
+        replace = ""
+    "#;
+
+    #[test]
+    fn test_apply() {
+        let rewrites: Rewrites = RULES.parse().unwrap();
+
+        assert_eq!(
+            Some("The Beatles".to_owned()),
+            rewrites.apply(Field::Artist, "Beatles, The"),
+        );
+        assert_eq!(None, rewrites.apply(Field::Title, "Beatles, The"));
+        assert_eq!(
+            Some("Help!".to_owned()),
+            rewrites.apply(Field::Title, "Help! (Remastered 2009)"),
+        );
+        assert_eq!(
+            Some("ABC".to_owned()),
+            rewrites.apply(Field::Artist, "ＡＢＣ"),
+        );
+        assert_eq!(None, rewrites.apply(Field::Album, "Help!"));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let missing_replace = "[[rule]]\nliteral = \"a\"";
+        assert!(matches!(
+            missing_replace.parse::<Rewrites>(),
+            Err(RewriteError::Rule { index: 1, .. }),
+        ));
+
+        let two_kinds = "[[rule]]\nliteral = \"a\"\nregex = \"a\"\nreplace = \"b\"";
+        assert!(matches!(
+            two_kinds.parse::<Rewrites>(),
+            Err(RewriteError::Rule { index: 1, .. }),
+        ));
+
+        let bad_regex = "[[rule]]\nregex = \"(\"\nreplace = \"b\"";
+        assert!(matches!(
+            bad_regex.parse::<Rewrites>(),
+            Err(RewriteError::Rule { index: 1, .. }),
+        ));
+
+        let unknown_field = "[[rule]]\nfields = [\"genre\"]\nnormalize = \"nfc\"";
+        assert!(matches!(
+            unknown_field.parse::<Rewrites>(),
+            Err(RewriteError::Parse(_)),
+        ));
+    }
+}
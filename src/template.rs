@@ -0,0 +1,193 @@
+use std::ffi::OsString;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Template for the name of lyrics files, without the extension. `.lrc` and `.nolrc` are
+/// appended to the result of [`LrcTemplate::expand`]
+///
+/// Supported placeholders are:
+/// - `{stem}`: name of the track file without its extension, e.g. `song` for `song.flac`
+/// - `{filename}`: full name of the track file, e.g. `song.flac`
+/// - `{artist}`, `{title}`, `{track}`: values read from the track tags
+/// - `{lang}`: language suffix, e.g. `.en`, or nothing if no language is set
+#[derive(Clone, Debug, PartialEq)]
+pub struct LrcTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Stem,
+    Filename,
+    Artist,
+    Title,
+    Track,
+    Lang,
+}
+
+/// Tag values that can be substituted into a [`LrcTemplate`]
+#[derive(Debug, Default)]
+pub struct Fields<'a> {
+    pub artist: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub track: Option<u32>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum TemplateError {
+    #[error("template is empty")]
+    Empty,
+    #[error("unknown placeholder \"{{{0}}}\"")]
+    UnknownPlaceholder(String),
+    #[error("unclosed placeholder")]
+    Unclosed,
+    #[error("template can't contain path separators")]
+    Separator,
+}
+
+impl FromStr for LrcTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(TemplateError::Empty);
+        }
+        if s.contains(['/', '\\']) {
+            return Err(TemplateError::Separator);
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+
+            let end = rest[start..].find('}').ok_or(TemplateError::Unclosed)? + start;
+            let part = match &rest[start + 1..end] {
+                "stem" => Part::Stem,
+                "filename" => Part::Filename,
+                "artist" => Part::Artist,
+                "title" => Part::Title,
+                "track" => Part::Track,
+                "lang" => Part::Lang,
+                other => return Err(TemplateError::UnknownPlaceholder(other.to_owned())),
+            };
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+impl LrcTemplate {
+    /// Whether expanding this template requires reading the tags of the track first
+    pub fn needs_tags(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Artist | Part::Title | Part::Track))
+    }
+
+    /// Expands this template for a track located at `path`. Tag values are sanitized, so that
+    /// they can't introduce path separators into the result
+    pub fn expand(&self, path: &Path, fields: &Fields, lang: Option<&str>) -> OsString {
+        let mut res = OsString::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => res.push(s),
+                Part::Stem => res.push(path.file_stem().unwrap_or_default()),
+                Part::Filename => res.push(path.file_name().unwrap_or_default()),
+                Part::Artist => res.push(sanitize(fields.artist.unwrap_or_default())),
+                Part::Title => res.push(sanitize(fields.title.unwrap_or_default())),
+                Part::Track => {
+                    if let Some(track) = fields.track {
+                        res.push(format!("{:02}", track));
+                    }
+                }
+                Part::Lang => {
+                    if let Some(lang) = lang {
+                        res.push(".");
+                        res.push(lang);
+                    }
+                }
+            }
+        }
+
+        res
+    }
+}
+
+fn sanitize(value: &str) -> String {
+    value.replace(['/', '\\', '\0'], "_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn expand(template: &str, path: &str, fields: &Fields, lang: Option<&str>) -> OsString {
+        template
+            .parse::<LrcTemplate>()
+            .unwrap()
+            .expand(Path::new(path), fields, lang)
+    }
+
+    #[test]
+    fn test_stem() {
+        let res = expand("{stem}", "/music/song.flac", &Fields::default(), None);
+        assert_eq!("song", res);
+    }
+
+    #[test]
+    fn test_filename_lang() {
+        let res = expand(
+            "{filename}{lang}",
+            "/music/song.flac",
+            &Fields::default(),
+            Some("en"),
+        );
+        assert_eq!("song.flac.en", res);
+    }
+
+    #[test]
+    fn test_tags() {
+        let fields = Fields {
+            artist: Some("AC/DC"),
+            title: Some("Thunderstruck"),
+            track: Some(1),
+        };
+        let res = expand(
+            "{track} - {artist} - {title}",
+            "/music/a.mp3",
+            &fields,
+            None,
+        );
+        assert_eq!("01 - AC_DC - Thunderstruck", res);
+    }
+
+    #[test]
+    fn test_needs_tags() {
+        assert!(!"{stem}{lang}".parse::<LrcTemplate>().unwrap().needs_tags());
+        assert!("{artist}".parse::<LrcTemplate>().unwrap().needs_tags());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Err(TemplateError::Empty), "".parse::<LrcTemplate>());
+        assert_eq!(Err(TemplateError::Unclosed), "{stem".parse::<LrcTemplate>());
+        assert_eq!(
+            Err(TemplateError::Separator),
+            "a/{stem}".parse::<LrcTemplate>()
+        );
+        assert_eq!(
+            Err(TemplateError::UnknownPlaceholder("album".to_owned())),
+            "{album}".parse::<LrcTemplate>(),
+        );
+    }
+}